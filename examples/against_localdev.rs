@@ -2,7 +2,10 @@ use licc::client::CodesClient;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    let client = CodesClient::new_full(None, Some("http://localhost:8000/v1".to_string()), None);
+    let client = CodesClient::builder()
+        .base_url("http://localhost:8000/v1")
+        .build()
+        .expect("Invalid client configuration");
 
     let result = client.get_codes_slim().await;
 
@@ -1,22 +1,19 @@
 use licc::{
     api_key::ApiKey,
     client::CodesClient,
-    write::{InsertCodeRequest, SourceLookup},
+    write::{expires_in, InsertCodeRequest, SourceLookup},
 };
+use std::time::Duration;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     let api_key = ApiKey::new("example".to_string());
-    let mut client = CodesClient::new(Some(api_key));
-
-    let current_epoch = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap();
+    let client = CodesClient::new(Some(api_key));
 
     let result = client
         .insert_code(InsertCodeRequest {
-            code: "FOOS-BARS-CODE".to_string(),
-            expires_at: current_epoch.as_secs() + 604800,
+            code: "FOOS-BARS-CODE".parse().unwrap(),
+            expires_at: expires_in(Duration::from_secs(604800)),
             creator: SourceLookup {
                 name: "Example Creator".to_string(),
                 url: "https://creator.example.org".to_string(),
@@ -0,0 +1,529 @@
+//! The `CodesApi` trait, so code can be written against any source of codes rather than `CodesClient`.
+//!
+//! Besides `CodesClient`, it is implemented by `StaticCodes` for tests, and by wrappers that add
+//! caching (`Cached`), retries (`Retrying`) and failover (`Failover`) to any other implementation.
+//!
+//! ```
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! use licc::api::{CodesApi, StaticCodes};
+//! use licc::client::error::ClientError;
+//!
+//! async fn active_codes(api: &impl CodesApi) -> Result<usize, ClientError> {
+//!     Ok(api.get_codes().await?.iter().filter(|code| !code.expired).count())
+//! }
+//!
+//! let codes = StaticCodes::new(vec![StaticCodes::code("FOOB-BARS-TEST")]);
+//! assert_eq!(active_codes(&codes).await.unwrap(), 1);
+//! # }
+//! ```
+
+use crate::client::error::ClientError;
+#[cfg(feature = "write")]
+use crate::client::error::{ErrorResponse, InnerErrorResponse};
+use crate::client::{CodesClient, RetryPolicy};
+#[cfg(feature = "write")]
+use crate::write;
+#[cfg(feature = "write")]
+use crate::Source;
+use crate::{Code, SourceIndex};
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// CodesApi is a source of codes, such as the remote service.
+#[async_trait]
+pub trait CodesApi: Send + Sync {
+    /// All codes, including their meta-information, see `CodesClient::get_codes`.
+    async fn get_codes(&self) -> Result<Vec<Code>, ClientError>;
+
+    /// All codes with only essential data, see `CodesClient::get_codes_slim`.
+    async fn get_codes_slim(&self) -> Result<Vec<Code>, ClientError> {
+        Ok(self.get_codes().await?.into_iter().map(slim).collect())
+    }
+
+    /// All sources, see `CodesClient::get_sources`.
+    async fn get_sources(&self) -> Result<SourceIndex, ClientError>;
+
+    /// Insert a code, returning its ID, see `CodesClient::insert_code`.
+    #[cfg(feature = "write")]
+    async fn insert_code(
+        &self,
+        insert_request: write::InsertCodeRequest,
+    ) -> Result<Option<i32>, ClientError>;
+}
+
+fn slim(code: Code) -> Code {
+    Code {
+        expires_at: None,
+        creator: None,
+        submitter: None,
+        lister: None,
+        ..code
+    }
+}
+
+#[async_trait]
+impl CodesApi for CodesClient {
+    async fn get_codes(&self) -> Result<Vec<Code>, ClientError> {
+        CodesClient::get_codes(self).await
+    }
+
+    async fn get_codes_slim(&self) -> Result<Vec<Code>, ClientError> {
+        CodesClient::get_codes_slim(self).await
+    }
+
+    async fn get_sources(&self) -> Result<SourceIndex, ClientError> {
+        CodesClient::get_sources(self).await
+    }
+
+    #[cfg(feature = "write")]
+    async fn insert_code(
+        &self,
+        insert_request: write::InsertCodeRequest,
+    ) -> Result<Option<i32>, ClientError> {
+        CodesClient::insert_code(self, insert_request).await
+    }
+}
+
+#[async_trait]
+impl<T: CodesApi + ?Sized> CodesApi for Arc<T> {
+    async fn get_codes(&self) -> Result<Vec<Code>, ClientError> {
+        (**self).get_codes().await
+    }
+
+    async fn get_codes_slim(&self) -> Result<Vec<Code>, ClientError> {
+        (**self).get_codes_slim().await
+    }
+
+    async fn get_sources(&self) -> Result<SourceIndex, ClientError> {
+        (**self).get_sources().await
+    }
+
+    #[cfg(feature = "write")]
+    async fn insert_code(
+        &self,
+        insert_request: write::InsertCodeRequest,
+    ) -> Result<Option<i32>, ClientError> {
+        (**self).insert_code(insert_request).await
+    }
+}
+
+/// StaticCodes is an in-memory list of codes, e.g. to unit test logic that uses a `CodesApi`.
+///
+/// Inserted codes are added to the list, inserting a code that is already listed fails with 409 Conflict.
+#[derive(Debug, Default)]
+pub struct StaticCodes {
+    codes: Mutex<Vec<Code>>,
+}
+
+impl StaticCodes {
+    pub fn new(codes: Vec<Code>) -> Self {
+        Self {
+            codes: Mutex::new(codes),
+        }
+    }
+
+    /// A code that has not expired, without an expiry timestamp or sources.
+    /// Panics if `code` is not a valid code.
+    pub fn code(code: &str) -> Code {
+        Code {
+            code: code.parse().expect("static code should be valid"),
+            expired: false,
+            expires_at: None,
+            creator: None,
+            submitter: None,
+            lister: None,
+        }
+    }
+
+    /// The codes currently listed, including inserted codes.
+    pub fn codes(&self) -> Vec<Code> {
+        lock(&self.codes).clone()
+    }
+}
+
+#[async_trait]
+impl CodesApi for StaticCodes {
+    async fn get_codes(&self) -> Result<Vec<Code>, ClientError> {
+        Ok(self.codes())
+    }
+
+    async fn get_sources(&self) -> Result<SourceIndex, ClientError> {
+        Ok(self
+            .codes()
+            .into_iter()
+            .flat_map(|code| [code.creator, code.submitter, code.lister])
+            .flatten()
+            .collect::<SourceIndex>())
+    }
+
+    #[cfg(feature = "write")]
+    async fn insert_code(
+        &self,
+        insert_request: write::InsertCodeRequest,
+    ) -> Result<Option<i32>, ClientError> {
+        let mut codes = lock(&self.codes);
+
+        if codes.iter().any(|code| code.code == insert_request.code) {
+            return Err(ClientError::ServerError(ErrorResponse {
+                error: InnerErrorResponse {
+                    code: 409,
+                    description: "Code already exists".to_string(),
+                    debug: None,
+                },
+            }));
+        }
+
+        let creator = Source {
+            id: 0,
+            name: insert_request.creator.name,
+            url: insert_request.creator.url,
+        };
+        codes.push(Code {
+            code: insert_request.code,
+            expired: false,
+            expires_at: None,
+            submitter: Some(creator.clone()),
+            creator: Some(creator),
+            lister: None,
+        });
+
+        Ok(Some(codes.len() as i32))
+    }
+}
+
+/// Cached remembers the codes and sources of another `CodesApi` for a while.
+///
+/// Unlike the `ResponseCache` of `CodesClient`, this does not ask the remote whether anything changed.
+pub struct Cached<A> {
+    inner: A,
+    ttl: Duration,
+    codes: Mutex<Option<(Instant, Vec<Code>)>>,
+    sources: Mutex<Option<(Instant, SourceIndex)>>,
+}
+
+impl<A: CodesApi> Cached<A> {
+    /// Remembers responses of `inner` for `ttl`.
+    pub fn new(inner: A, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            codes: Mutex::new(None),
+            sources: Mutex::new(None),
+        }
+    }
+
+    /// Forgets the remembered responses, the next request goes to the wrapped `CodesApi`.
+    pub fn invalidate(&self) {
+        *lock(&self.codes) = None;
+        *lock(&self.sources) = None;
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The remembered value if it is younger than `ttl`.
+fn fresh<T: Clone>(cached: &Mutex<Option<(Instant, T)>>, ttl: Duration) -> Option<T> {
+    match &*lock(cached) {
+        Some((at, value)) if at.elapsed() < ttl => Some(value.clone()),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl<A: CodesApi> CodesApi for Cached<A> {
+    async fn get_codes(&self) -> Result<Vec<Code>, ClientError> {
+        if let Some(codes) = fresh(&self.codes, self.ttl) {
+            return Ok(codes);
+        }
+
+        let codes = self.inner.get_codes().await?;
+        *lock(&self.codes) = Some((Instant::now(), codes.clone()));
+
+        Ok(codes)
+    }
+
+    async fn get_sources(&self) -> Result<SourceIndex, ClientError> {
+        if let Some(sources) = fresh(&self.sources, self.ttl) {
+            return Ok(sources);
+        }
+
+        let sources = self.inner.get_sources().await?;
+        *lock(&self.sources) = Some((Instant::now(), sources.clone()));
+
+        Ok(sources)
+    }
+
+    /// Inserting a code changes the list of codes, so the cache is invalidated.
+    #[cfg(feature = "write")]
+    async fn insert_code(
+        &self,
+        insert_request: write::InsertCodeRequest,
+    ) -> Result<Option<i32>, ClientError> {
+        let result = self.inner.insert_code(insert_request).await;
+        self.invalidate();
+
+        result
+    }
+}
+
+/// Retrying attempts requests to another `CodesApi` again when they fail in a way that is likely transient,
+/// as allowed by a `RetryPolicy`.
+///
+/// `CodesClient` can retry by itself (see `CodesClientBuilder::retry_policy`), this is for other implementations
+/// or to retry across a `Failover`.
+pub struct Retrying<A> {
+    inner: A,
+    retry_policy: RetryPolicy,
+}
+
+impl<A: CodesApi> Retrying<A> {
+    pub fn new(inner: A, retry_policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            retry_policy,
+        }
+    }
+
+    async fn retry<T, F, Fut>(&self, idempotent: bool, request: F) -> Result<T, ClientError>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: std::future::Future<Output = Result<T, ClientError>> + Send,
+    {
+        let mut attempt = 1;
+
+        loop {
+            let err = match request().await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+
+            let retry_after = match &err {
+                ClientError::RateLimited { retry_after } => *retry_after,
+                _ => None,
+            };
+
+            let delay = match self.retry_policy.delay(attempt, retry_after) {
+                Some(delay)
+                    if self.retry_policy.allows(idempotent)
+                        && attempt < self.retry_policy.max_attempts()
+                        && err.is_retryable() =>
+                {
+                    delay
+                }
+                _ => {
+                    return Err(match attempt {
+                        1 => err,
+                        attempts => ClientError::RetriesExhausted {
+                            attempts,
+                            last: Box::new(err),
+                        },
+                    });
+                }
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[async_trait]
+impl<A: CodesApi> CodesApi for Retrying<A> {
+    async fn get_codes(&self) -> Result<Vec<Code>, ClientError> {
+        self.retry(true, || self.inner.get_codes()).await
+    }
+
+    async fn get_codes_slim(&self) -> Result<Vec<Code>, ClientError> {
+        self.retry(true, || self.inner.get_codes_slim()).await
+    }
+
+    async fn get_sources(&self) -> Result<SourceIndex, ClientError> {
+        self.retry(true, || self.inner.get_sources()).await
+    }
+
+    #[cfg(feature = "write")]
+    async fn insert_code(
+        &self,
+        insert_request: write::InsertCodeRequest,
+    ) -> Result<Option<i32>, ClientError> {
+        self.retry(false, || self.inner.insert_code(insert_request.clone()))
+            .await
+    }
+}
+
+/// Failover sends requests to `secondary` when `primary` fails in a way that is likely transient,
+/// such as being unreachable or responding with a server error.
+pub struct Failover<A, B> {
+    primary: A,
+    secondary: B,
+}
+
+impl<A: CodesApi, B: CodesApi> Failover<A, B> {
+    pub fn new(primary: A, secondary: B) -> Self {
+        Self { primary, secondary }
+    }
+}
+
+#[async_trait]
+impl<A: CodesApi, B: CodesApi> CodesApi for Failover<A, B> {
+    async fn get_codes(&self) -> Result<Vec<Code>, ClientError> {
+        match self.primary.get_codes().await {
+            Err(err) if err.is_retryable() => self.secondary.get_codes().await,
+            result => result,
+        }
+    }
+
+    async fn get_codes_slim(&self) -> Result<Vec<Code>, ClientError> {
+        match self.primary.get_codes_slim().await {
+            Err(err) if err.is_retryable() => self.secondary.get_codes_slim().await,
+            result => result,
+        }
+    }
+
+    async fn get_sources(&self) -> Result<SourceIndex, ClientError> {
+        match self.primary.get_sources().await {
+            Err(err) if err.is_retryable() => self.secondary.get_sources().await,
+            result => result,
+        }
+    }
+
+    /// Inserts are only sent to `secondary` if `primary` could not be reached at all,
+    /// otherwise `primary` may have inserted the code regardless of the error.
+    #[cfg(feature = "write")]
+    async fn insert_code(
+        &self,
+        insert_request: write::InsertCodeRequest,
+    ) -> Result<Option<i32>, ClientError> {
+        match self.primary.insert_code(insert_request.clone()).await {
+            Err(ClientError::Reqwest(err)) if err.is_connect() => {
+                self.secondary.insert_code(insert_request).await
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Source;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails with 503 until it was asked `failures` times.
+    struct Flaky {
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    impl Flaky {
+        fn new(failures: u32) -> Self {
+            Self {
+                failures,
+                calls: AtomicU32::new(0),
+            }
+        }
+
+        fn calls(&self) -> u32 {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl CodesApi for Flaky {
+        async fn get_codes(&self) -> Result<Vec<Code>, ClientError> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(ClientError::UnexpectedResponse {
+                    status: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+                    body: String::new(),
+                });
+            }
+            Ok(vec![StaticCodes::code("FOOB-BARS-TEST")])
+        }
+
+        async fn get_sources(&self) -> Result<SourceIndex, ClientError> {
+            Ok(SourceIndex::default())
+        }
+
+        #[cfg(feature = "write")]
+        async fn insert_code(
+            &self,
+            _insert_request: write::InsertCodeRequest,
+        ) -> Result<Option<i32>, ClientError> {
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_static_codes_slim() {
+        let mut code = StaticCodes::code("FOOB-BARS-TEST");
+        code.creator = Some(Source {
+            id: 1,
+            name: "foo".to_string(),
+            url: "https://foo.example".to_string(),
+        });
+        let codes = StaticCodes::new(vec![code]);
+
+        assert!(codes.get_codes().await.unwrap()[0].creator.is_some());
+        assert!(codes.get_codes_slim().await.unwrap()[0].creator.is_none());
+        assert_eq!(codes.get_sources().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "write")]
+    async fn test_static_codes_insert() {
+        let codes = StaticCodes::default();
+        let request = write::InsertCodeRequest {
+            code: "FOOB-BARS-TEST".parse().unwrap(),
+            expires_at: write::expires_in(Duration::from_secs(60)),
+            creator: write::SourceLookup {
+                name: "foo".to_string(),
+                url: "https://foo.example".to_string(),
+            },
+            submitter: None,
+        };
+
+        assert_eq!(codes.insert_code(request.clone()).await.unwrap(), Some(1));
+        assert!(codes.insert_code(request).await.unwrap_err().is_conflict());
+        assert_eq!(codes.codes().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cached() {
+        let cached = Cached::new(Flaky::new(0), Duration::from_secs(60));
+
+        cached.get_codes().await.unwrap();
+        cached.get_codes().await.unwrap();
+        assert_eq!(cached.inner.calls(), 1);
+
+        cached.invalidate();
+        cached.get_codes().await.unwrap();
+        assert_eq!(cached.inner.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retrying() {
+        let policy = RetryPolicy::exponential(3).initial_backoff(Duration::from_millis(1));
+
+        let retrying = Retrying::new(Flaky::new(2), policy.clone());
+        assert!(retrying.get_codes().await.is_ok());
+        assert_eq!(retrying.inner.calls(), 3);
+
+        let retrying = Retrying::new(Flaky::new(5), policy);
+        assert!(matches!(
+            retrying.get_codes().await,
+            Err(ClientError::RetriesExhausted { attempts: 3, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let failover = Failover::new(Flaky::new(1), StaticCodes::default());
+        assert!(failover.get_codes().await.unwrap().is_empty());
+        assert!(!failover.get_codes().await.unwrap().is_empty());
+    }
+}
@@ -1,38 +1,261 @@
-use std::env::VarError;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
 
 /// API Key for the remote service.
 /// This is required for PUT/POST requests.
 ///
 /// If you believe you need an API Key,
 /// contact the maintainer of the remote service you are using.
-pub struct ApiKey(String);
+///
+/// The key is overwritten in memory when dropped, never printed (it formats as `ApiKey(***)`),
+/// and compared in constant time.
+#[derive(Clone)]
+pub struct ApiKey(Zeroizing<String>);
+
+/// The reasons loading an `ApiKey` can fail.
+#[derive(Debug)]
+pub enum ApiKeyError {
+    /// The environment variable, or the variable in the dotenv file, is not set
+    NotFound { name: String },
+    /// The key is not valid unicode
+    NotUnicode,
+    /// The key is empty
+    Empty,
+    /// Reading the file or running the command failed
+    Io(std::io::Error),
+    /// The file is readable by other users, restrict it with e.g. `chmod 600`
+    InsecurePermissions { path: PathBuf, mode: u32 },
+    /// The command exited unsuccessfully
+    CommandFailed { program: String, status: ExitStatus },
+}
 
 impl ApiKey {
     pub fn new(key: String) -> Self {
-        Self(key)
+        Self(Zeroizing::new(key))
     }
 
     /// Attempts to load an API Key from the environment.
-    /// If the environment variable is not set, this will return an error.
-    pub fn from_env(env_name: &'static str) -> Result<ApiKey, VarError> {
-        Ok(Self(std::env::var(env_name)?))
+    /// If the environment variable is not set or empty, this will return an error.
+    pub fn from_env(env_name: &str) -> Result<ApiKey, ApiKeyError> {
+        match std::env::var(env_name) {
+            Ok(key) => Self::parse(Zeroizing::new(key)),
+            Err(std::env::VarError::NotPresent) => Err(ApiKeyError::NotFound {
+                name: env_name.to_string(),
+            }),
+            Err(std::env::VarError::NotUnicode(_)) => Err(ApiKeyError::NotUnicode),
+        }
+    }
+
+    /// Reads an API Key from a file containing only the key, surrounding whitespace is ignored.
+    ///
+    /// On unix, files that other users can read are rejected.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<ApiKey, ApiKeyError> {
+        let path = path.as_ref();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = std::fs::metadata(path)
+                .map_err(ApiKeyError::Io)?
+                .permissions()
+                .mode();
+            if mode & 0o004 != 0 {
+                return Err(ApiKeyError::InsecurePermissions {
+                    path: path.to_path_buf(),
+                    mode: mode & 0o777,
+                });
+            }
+        }
+
+        Self::parse(Self::read(path)?)
+    }
+
+    /// Reads the variable `name` from a dotenv-style file of `NAME=value` lines.
+    /// Comments, `export` prefixes and quoted values are supported, variable expansion is not.
+    pub fn from_dotenv(path: impl AsRef<Path>, name: &str) -> Result<ApiKey, ApiKeyError> {
+        let contents = Self::read(path.as_ref())?;
+
+        for line in contents.lines() {
+            let line = line.trim();
+            let line = line.strip_prefix("export ").unwrap_or(line);
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) if !line.starts_with('#') => (key.trim(), value.trim()),
+                _ => continue,
+            };
+
+            if key == name {
+                return Self::parse(Zeroizing::new(unquote(value).to_string()));
+            }
+        }
+
+        Err(ApiKeyError::NotFound {
+            name: name.to_string(),
+        })
+    }
+
+    /// Runs a command and uses the first line it prints as API Key, e.g. `pass show licc/api-key`.
+    ///
+    /// The command inherits stdin and stderr, so it can prompt for a passphrase.
+    pub fn from_command<I, S>(program: impl AsRef<OsStr>, args: I) -> Result<ApiKey, ApiKeyError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let program = program.as_ref();
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(ApiKeyError::Io)?;
+        let stdout = Zeroizing::new(output.stdout);
+
+        if !output.status.success() {
+            return Err(ApiKeyError::CommandFailed {
+                program: program.to_string_lossy().into_owned(),
+                status: output.status,
+            });
+        }
+
+        let stdout = std::str::from_utf8(&stdout).map_err(|_| ApiKeyError::NotUnicode)?;
+
+        Self::parse(Zeroizing::new(
+            stdout.lines().next().unwrap_or_default().to_string(),
+        ))
     }
 
     pub fn get(&self) -> &str {
         &self.0
     }
+
+    fn read(path: &Path) -> Result<Zeroizing<String>, ApiKeyError> {
+        let bytes = Zeroizing::new(std::fs::read(path).map_err(ApiKeyError::Io)?);
+        let contents = std::str::from_utf8(&bytes).map_err(|_| ApiKeyError::NotUnicode)?;
+
+        Ok(Zeroizing::new(contents.to_string()))
+    }
+
+    fn parse(key: Zeroizing<String>) -> Result<ApiKey, ApiKeyError> {
+        let trimmed = key.trim();
+
+        match trimmed.len() {
+            0 => Err(ApiKeyError::Empty),
+            len if len == key.len() => Ok(Self(key)),
+            _ => Ok(Self::new(trimmed.to_string())),
+        }
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|value| value.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+
+    // Unquoted values may be followed by a comment.
+    value.split(" #").next().unwrap_or_default().trim_end()
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ApiKey(***)")
+    }
+}
+
+impl fmt::Display for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ApiKey(***)")
+    }
+}
+
+impl PartialEq for ApiKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_bytes().ct_eq(other.0.as_bytes()).into()
+    }
+}
+
+impl Eq for ApiKey {}
+
+impl fmt::Display for ApiKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound { name } => write!(f, "the API key variable {} is not set", name),
+            Self::NotUnicode => f.write_str("the API key is not valid unicode"),
+            Self::Empty => f.write_str("the API key is empty"),
+            Self::Io(_) => f.write_str("failed to read the API key"),
+            Self::InsecurePermissions { path, mode } => write!(
+                f,
+                "the API key file {} is readable by other users (mode {:o})",
+                path.display(),
+                mode
+            ),
+            Self::CommandFailed { program, status } => {
+                write!(f, "the API key command {} failed ({})", program, status)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApiKeyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("licc-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        path
+    }
+
     #[test]
     fn test_api_key() {
         let key = ApiKey::new("test".to_string());
         assert_eq!(key.get(), "test");
     }
 
+    #[test]
+    fn test_api_key_is_redacted() {
+        let key = ApiKey::new("test".to_string());
+
+        assert_eq!(format!("{:?}", key), "ApiKey(***)");
+        assert_eq!(format!("{}", key), "ApiKey(***)");
+        assert_eq!(format!("{:?}", Some(key)), "Some(ApiKey(***))");
+    }
+
+    #[test]
+    fn test_api_key_eq() {
+        let key = ApiKey::new("test".to_string());
+
+        assert_eq!(key, ApiKey::new("test".to_string()));
+        assert_ne!(key, ApiKey::new("tess".to_string()));
+        assert_ne!(key, ApiKey::new("test2".to_string()));
+    }
+
     #[test]
     fn test_from_env_ok() {
         std::env::set_var("CODES__TEST_API_KEY", "test");
@@ -42,6 +265,70 @@ mod test {
 
     #[test]
     fn test_from_env_err() {
-        assert!(ApiKey::from_env("CODES__TEST_API_KEY_NOT_SET").is_err())
+        assert!(matches!(
+            ApiKey::from_env("CODES__TEST_API_KEY_NOT_SET"),
+            Err(ApiKeyError::NotFound { .. })
+        ));
+
+        std::env::set_var("CODES__TEST_API_KEY_EMPTY", " ");
+        assert!(matches!(
+            ApiKey::from_env("CODES__TEST_API_KEY_EMPTY"),
+            Err(ApiKeyError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_from_file() {
+        let path = temp_file("from-file", "secret\n");
+        assert_eq!(ApiKey::from_file(&path).unwrap().get(), "secret");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+            assert!(matches!(
+                ApiKey::from_file(&path),
+                Err(ApiKeyError::InsecurePermissions { mode: 0o644, .. })
+            ));
+        }
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(ApiKey::from_file(&path), Err(ApiKeyError::Io(_))));
+    }
+
+    #[test]
+    fn test_from_dotenv() {
+        let path = temp_file(
+            "dotenv",
+            "# comment\nOTHER=foo\nexport LICC_API_KEY=\"secret\"\nUNQUOTED=bar # comment\n",
+        );
+
+        assert_eq!(
+            ApiKey::from_dotenv(&path, "LICC_API_KEY").unwrap().get(),
+            "secret"
+        );
+        assert_eq!(ApiKey::from_dotenv(&path, "UNQUOTED").unwrap().get(), "bar");
+        assert!(matches!(
+            ApiKey::from_dotenv(&path, "MISSING"),
+            Err(ApiKeyError::NotFound { .. })
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_from_command() {
+        assert_eq!(
+            ApiKey::from_command("printf", ["secret\\nsecond line"])
+                .unwrap()
+                .get(),
+            "secret"
+        );
+        assert!(matches!(
+            ApiKey::from_command("false", Vec::<&str>::new()),
+            Err(ApiKeyError::CommandFailed { .. })
+        ));
     }
 }
@@ -0,0 +1,531 @@
+//! `licc` lists, watches and submits Idle Champions codes from the command line.
+//!
+//! Settings are taken from flags, then environment variables, then the config file
+//! (`$XDG_CONFIG_HOME/licc/config.toml` or `~/.config/licc/config.toml`, unless `--config` is given).
+//! The API key is only read from the `LICC_API_KEY` environment variable.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use futures_util::StreamExt;
+use licc::api_key::ApiKey;
+use licc::client::error::ClientError;
+use licc::client::CodesClient;
+use licc::watch::{CodeWatcher, WatchEvent};
+use licc::{Code, Source};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+/// Environment variable the API key is read from
+const API_KEY_ENV: &str = "LICC_API_KEY";
+
+#[derive(Parser)]
+#[command(
+    name = "licc",
+    version,
+    about = "List, watch and submit Idle Champions codes"
+)]
+struct Cli {
+    /// Base URL of the codes API, e.g. `https://codes.idlechampions.liefland.net/v1`
+    #[arg(long, env = "LICC_BASE_URL", global = true)]
+    base_url: Option<String>,
+    /// Request timeout in seconds
+    #[arg(long, env = "LICC_TIMEOUT", global = true)]
+    timeout: Option<u64>,
+    /// Path of the config file
+    #[arg(long, env = "LICC_CONFIG", global = true)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the codes the remote knows about
+    List {
+        /// Only list codes that have not expired
+        #[arg(long, conflicts_with = "expired")]
+        active: bool,
+        /// Only list codes that have expired
+        #[arg(long)]
+        expired: bool,
+        /// Only list codes by creators whose name contains this, ignoring case
+        #[arg(long)]
+        creator: Option<String>,
+        #[arg(long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+    /// Print codes as they are added to the remote
+    Watch {
+        /// Seconds between checking for new codes
+        #[arg(long)]
+        interval: Option<u64>,
+        /// Also print the codes that are already known when starting
+        #[arg(long)]
+        include_existing: bool,
+        /// Print every code as a line of JSON instead of only the code
+        #[arg(long)]
+        json: bool,
+    },
+    /// Submit codes, from arguments or one code per line from a file or stdin
+    #[cfg(feature = "write")]
+    Insert {
+        /// The codes to insert, read from stdin if none are given and no file is set
+        codes: Vec<String>,
+        /// Read codes from this file, one per line
+        #[arg(long, conflicts_with = "codes")]
+        file: Option<PathBuf>,
+        /// Name of the creator of the codes
+        #[arg(long)]
+        creator_name: String,
+        /// URL of the creator of the codes
+        #[arg(long)]
+        creator_url: String,
+        /// Days until the codes expire
+        #[arg(long, default_value_t = 7)]
+        expires_in: u64,
+    },
+    /// List the sources the remote knows about
+    Sources {
+        #[arg(long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Plain,
+}
+
+/// The config file, all settings are optional.
+#[derive(Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    base_url: Option<String>,
+    /// Request timeout in seconds
+    timeout: Option<u64>,
+    /// Seconds between checking for new codes in `watch`
+    interval: Option<u64>,
+}
+
+/// An error that ends the program, with the exit code to end it with.
+struct Failure {
+    message: String,
+    code: u8,
+}
+
+impl From<ClientError> for Failure {
+    fn from(err: ClientError) -> Self {
+        let mut message = err.to_string();
+        let mut source = std::error::Error::source(&err);
+        while let Some(err) = source {
+            // Some errors already include the message of their source.
+            let cause = err.to_string();
+            if !message.contains(&cause) {
+                message = format!("{}: {}", message, cause);
+            }
+            source = err.source();
+        }
+
+        Self {
+            message,
+            code: exit_code(&err),
+        }
+    }
+}
+
+/// Maps the kind of error to an exit code, so scripts can tell failures apart.
+fn exit_code(err: &ClientError) -> u8 {
+    if err.is_unauthorized() {
+        return 4;
+    }
+    if err.is_not_found() {
+        return 5;
+    }
+    if err.is_timeout() {
+        return 3;
+    }
+
+    match err {
+        #[cfg(feature = "write")]
+        ClientError::ApiKeyMissing => 4,
+        ClientError::Authentication { .. } => 4,
+        ClientError::InvalidBaseUrl { .. } => 2,
+        ClientError::InvalidRateLimit { .. } => 2,
+        ClientError::Reqwest(_) => 3,
+        ClientError::RateLimited { .. } => 6,
+        ClientError::Serde(_) => 8,
+        ClientError::RetriesExhausted { last, .. } => exit_code(last),
+        _ if err.status_code().is_some_and(|s| s.is_server_error()) => 7,
+        _ => 1,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(code) => code,
+        Err(failure) => {
+            eprintln!("licc: {}", failure.message);
+            ExitCode::from(failure.code)
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<ExitCode, Failure> {
+    let config = load_config(cli.config.as_deref())?;
+    let client = client(&cli, &config)?;
+
+    match cli.command {
+        Command::List {
+            active,
+            expired,
+            creator,
+            format,
+        } => {
+            let codes = client
+                .get_codes()
+                .await?
+                .into_iter()
+                .filter(|code| !active || !code.expired)
+                .filter(|code| !expired || code.expired)
+                .filter(|code| match &creator {
+                    Some(creator) => code.creator.as_ref().is_some_and(|source| {
+                        source.name.to_lowercase().contains(&creator.to_lowercase())
+                    }),
+                    None => true,
+                })
+                .collect::<Vec<Code>>();
+
+            print_codes(&codes, format);
+        }
+        Command::Watch {
+            interval,
+            include_existing,
+            json,
+        } => {
+            let format = match json {
+                true => Format::Json,
+                false => Format::Plain,
+            };
+            let interval = interval.or(config.interval).unwrap_or(300);
+            let mut watcher =
+                CodeWatcher::new(client.clone()).interval(Duration::from_secs(interval));
+            if !include_existing {
+                watcher = watcher.seed(client.get_codes().await?);
+            }
+
+            let events = watcher.into_stream();
+            futures_util::pin_mut!(events);
+            while let Some(event) = events.next().await {
+                match event {
+                    Ok(WatchEvent::CodeAdded(code)) => print_codes(&[code], format),
+                    Ok(_) => {}
+                    // Keep watching, the remote may be back by the next check.
+                    Err(err) => eprintln!("licc: {}", Failure::from(err).message),
+                }
+            }
+        }
+        #[cfg(feature = "write")]
+        Command::Insert {
+            codes,
+            file,
+            creator_name,
+            creator_url,
+            expires_in,
+        } => return insert(&client, codes, file, creator_name, creator_url, expires_in).await,
+        Command::Sources { format } => {
+            let sources = client.get_sources().await?;
+            print_sources(&sources.iter().collect::<Vec<&Source>>(), format);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(feature = "write")]
+async fn insert(
+    client: &CodesClient,
+    codes: Vec<String>,
+    file: Option<PathBuf>,
+    creator_name: String,
+    creator_url: String,
+    expires_in: u64,
+) -> Result<ExitCode, Failure> {
+    use licc::write::{self, InsertCodeRequest, SourceLookup};
+    use std::io::Read;
+
+    let lines = match (codes.is_empty(), file) {
+        (false, _) => codes,
+        (true, Some(file)) => {
+            read_lines(&std::fs::read_to_string(&file).map_err(|err| Failure {
+                message: format!("cannot read {}: {}", file.display(), err),
+                code: 1,
+            })?)
+        }
+        (true, None) => {
+            let mut input = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|err| Failure {
+                    message: format!("cannot read stdin: {}", err),
+                    code: 1,
+                })?;
+            read_lines(&input)
+        }
+    };
+
+    let expires_in = expires_in.checked_mul(24 * 60 * 60).ok_or(Failure {
+        message: format!("--expires-in {} is too large", expires_in),
+        code: 2,
+    })?;
+    let expires_at = write::expires_in(Duration::from_secs(expires_in));
+    let creator = SourceLookup {
+        name: creator_name,
+        url: creator_url,
+    };
+
+    let mut requests = Vec::with_capacity(lines.len());
+    for line in lines {
+        let code = line.parse().map_err(|err| Failure {
+            message: format!("{:?} is not a valid code: {}", line, err),
+            code: 2,
+        })?;
+        requests.push(InsertCodeRequest {
+            code,
+            expires_at,
+            creator: creator.clone(),
+            submitter: None,
+        });
+    }
+
+    let report = client.insert_codes(requests).await?;
+
+    for (code, id) in report.inserted() {
+        match id {
+            Some(id) => println!("inserted {} ({})", code, id),
+            None => println!("inserted {}", code),
+        }
+    }
+    for code in report.conflicts() {
+        println!("exists {}", code);
+    }
+
+    let mut exit = ExitCode::SUCCESS;
+    for (code, err) in report.failed() {
+        eprintln!("failed {}: {}", code, err);
+        exit = ExitCode::from(exit_code(err));
+    }
+
+    Ok(exit)
+}
+
+/// The non-empty lines of `input` that are not comments (starting with `#`), trimmed.
+#[cfg(feature = "write")]
+fn read_lines(input: &str) -> Vec<String> {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The client as configured by flags, environment variables and the config file.
+fn client(cli: &Cli, config: &Config) -> Result<CodesClient, Failure> {
+    let mut builder = CodesClient::builder();
+
+    if let Some(base_url) = cli.base_url.as_ref().or(config.base_url.as_ref()) {
+        builder = builder.base_url(base_url);
+    }
+    if let Some(timeout) = cli.timeout.or(config.timeout) {
+        builder = builder.timeout(Duration::from_secs(timeout));
+    }
+    if let Ok(api_key) = ApiKey::from_env(API_KEY_ENV) {
+        builder = builder.api_key(api_key);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Reads the config file at `path`, or at the default location if it exists.
+fn load_config(path: Option<&Path>) -> Result<Config, Failure> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => match default_config_path().filter(|path| path.exists()) {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        },
+    };
+
+    let contents = std::fs::read_to_string(&path).map_err(|err| Failure {
+        message: format!("cannot read config {}: {}", path.display(), err),
+        code: 2,
+    })?;
+
+    toml::from_str(&contents).map_err(|err| Failure {
+        message: format!("invalid config {}: {}", path.display(), err),
+        code: 2,
+    })
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join("licc").join("config.toml"))
+}
+
+fn print_codes(codes: &[Code], format: Format) {
+    match format {
+        Format::Plain => {
+            for code in codes {
+                println!("{}", code.code);
+            }
+        }
+        Format::Json => {
+            for code in codes {
+                println!("{}", code_json(code));
+            }
+        }
+        Format::Table => print_table(
+            &["CODE", "EXPIRED", "EXPIRES AT", "CREATOR"],
+            codes
+                .iter()
+                .map(|code| {
+                    vec![
+                        code.code.to_string(),
+                        code.expired.to_string(),
+                        code.expires_at.clone().unwrap_or_default(),
+                        code.creator
+                            .as_ref()
+                            .map(|s| s.name.clone())
+                            .unwrap_or_default(),
+                    ]
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn code_json(code: &Code) -> serde_json::Value {
+    let source = |source: &Option<Source>| {
+        source
+            .as_ref()
+            .map(source_json)
+            .unwrap_or(serde_json::Value::Null)
+    };
+
+    serde_json::json!({
+        "code": code.code,
+        "expired": code.expired,
+        "expires_at": code.expires_at,
+        "creator": source(&code.creator),
+        "submitter": source(&code.submitter),
+        "lister": source(&code.lister),
+    })
+}
+
+fn source_json(source: &Source) -> serde_json::Value {
+    serde_json::json!({
+        "id": source.id,
+        "name": source.name,
+        "url": source.url,
+    })
+}
+
+fn print_sources(sources: &[&Source], format: Format) {
+    match format {
+        Format::Plain => {
+            for source in sources {
+                println!("{}", source.name);
+            }
+        }
+        Format::Json => {
+            for source in sources {
+                println!("{}", source_json(source));
+            }
+        }
+        Format::Table => print_table(
+            &["ID", "NAME", "URL"],
+            sources
+                .iter()
+                .map(|s| vec![s.id.to_string(), s.name.clone(), s.url.clone()])
+                .collect(),
+        ),
+    }
+}
+
+/// Prints rows aligned in columns, the last column is not padded.
+fn print_table(header: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header = header.iter().map(|h| h.to_string()).collect();
+    for row in std::iter::once(header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config =
+            toml::from_str("base_url = \"http://localhost:8000/v1\"\ninterval = 60").unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                base_url: Some("http://localhost:8000/v1".to_string()),
+                timeout: None,
+                interval: Some(60),
+            }
+        );
+        assert!(toml::from_str::<Config>("api_key = \"foo\"").is_err());
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let unexpected = |status| ClientError::UnexpectedResponse {
+            status,
+            body: String::new(),
+        };
+
+        assert_eq!(exit_code(&unexpected(reqwest::StatusCode::FORBIDDEN)), 4);
+        assert_eq!(exit_code(&unexpected(reqwest::StatusCode::NOT_FOUND)), 5);
+        assert_eq!(
+            exit_code(&unexpected(reqwest::StatusCode::GATEWAY_TIMEOUT)),
+            3
+        );
+        assert_eq!(exit_code(&unexpected(reqwest::StatusCode::BAD_GATEWAY)), 7);
+        assert_eq!(exit_code(&unexpected(reqwest::StatusCode::BAD_REQUEST)), 1);
+        assert_eq!(
+            exit_code(&ClientError::RateLimited { retry_after: None }),
+            6
+        );
+    }
+
+    #[test]
+    fn test_cli() {
+        use clap::CommandFactory;
+
+        Cli::command().debug_assert();
+    }
+}
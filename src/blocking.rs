@@ -0,0 +1,306 @@
+//! A synchronous client for applications that do not run an async runtime, such as CLI tools or build scripts.
+//!
+//! It mirrors the async `client::CodesClient` and returns the same models and errors.
+//! Requires the `blocking` feature.
+#![cfg(feature = "blocking")]
+
+use crate::api_key::ApiKey;
+use crate::client::error::ClientError;
+#[cfg(feature = "write")]
+use crate::client::puts;
+use crate::client::{self, DEFAULT_BASE_URL};
+#[cfg(feature = "write")]
+use crate::client::{ApiKeyHeader, Authenticator};
+#[cfg(feature = "write")]
+use crate::write;
+#[cfg(feature = "write")]
+use crate::ChestCode;
+use crate::Code;
+#[cfg(feature = "write")]
+use std::sync::Arc;
+
+pub struct CodesClient {
+    base_url: String,
+    #[allow(dead_code)]
+    api_key: Option<ApiKey>,
+    #[cfg(feature = "write")]
+    authenticator: Option<Arc<dyn Authenticator>>,
+    client: reqwest::blocking::Client,
+}
+
+impl CodesClient {
+    /// Construct a new CodesClient providing an optional API key
+    /// If no values need to ever change - or if the `write` feature is always disabled,
+    /// consider using `default` instead.
+    pub fn new(api_key: Option<ApiKey>) -> Self {
+        Self::new_full(api_key, None, None)
+    }
+
+    /// Construct a new CodesClient, optionally providing an API Key.
+    /// If left to None, default values will be used.
+    /// If no values need to change, consider using `default` instead.
+    pub fn new_full(
+        api_key: Option<ApiKey>,
+        base_url: Option<String>,
+        client: Option<reqwest::blocking::Client>,
+    ) -> Self {
+        Self {
+            base_url: base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            client: client.unwrap_or_else(Self::default_client),
+            api_key,
+            #[cfg(feature = "write")]
+            authenticator: None,
+        }
+    }
+
+    /// How write requests are authenticated, see `client::CodesClientBuilder::authenticator`.
+    /// Takes precedence over the API key.
+    #[cfg(feature = "write")]
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        if path.starts_with('/') {
+            return format!("{}{}", self.base_url, path);
+        }
+        format!("{}/{}", self.base_url, path)
+    }
+
+    /// Perform any arbitrary GET request and take ownership of deserializing the response.
+    pub fn get(&self, route: &str) -> Result<String, ClientError> {
+        let response = self
+            .client
+            .get(self.url(route))
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .send()
+            .map_err(ClientError::Reqwest)?;
+
+        self.response(response)
+    }
+
+    /// Sends a request that modifies the remote, which requires an API key or `Authenticator`.
+    #[cfg(feature = "write")]
+    fn send_write(
+        &self,
+        method: reqwest::Method,
+        route: &str,
+        body: Option<&str>,
+    ) -> Result<String, ClientError> {
+        let authenticator = self.authenticator()?;
+
+        let request = self
+            .client
+            .request(method, self.url(route))
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json");
+        let request = match body {
+            Some(body) => request.body(body.to_string()),
+            None => request,
+        };
+        let mut request = request.build().map_err(ClientError::Reqwest)?;
+
+        // Authenticators work on async requests, so they authenticate a copy and the headers are taken over.
+        let mut copy = reqwest::Request::new(request.method().clone(), request.url().clone());
+        *copy.body_mut() = body.map(|body| body.to_string().into());
+        authenticator.authenticate(&mut copy)?;
+        request.headers_mut().extend(copy.headers().clone());
+
+        self.response(self.client.execute(request).map_err(ClientError::Reqwest)?)
+    }
+
+    /// The configured `Authenticator`, or one sending the API key.
+    #[cfg(feature = "write")]
+    fn authenticator(&self) -> Result<Arc<dyn Authenticator>, ClientError> {
+        if let Some(authenticator) = &self.authenticator {
+            return Ok(authenticator.clone());
+        }
+
+        match &self.api_key {
+            Some(api_key) => Ok(Arc::new(ApiKeyHeader(api_key.clone()))),
+            None => Err(ClientError::ApiKeyMissing),
+        }
+    }
+
+    #[cfg(feature = "write")]
+    /// Perform any arbitrary PUT request and take ownership of deserializing the response.
+    /// These actions typically require an API key.
+    pub fn put(&self, route: &str, body: &str) -> Result<String, ClientError> {
+        self.send_write(reqwest::Method::PUT, route, Some(body))
+    }
+
+    #[cfg(feature = "write")]
+    /// Perform any arbitrary POST request and take ownership of deserializing the response.
+    /// These actions typically require an API key.
+    pub fn post(&self, route: &str, body: &str) -> Result<String, ClientError> {
+        self.send_write(reqwest::Method::POST, route, Some(body))
+    }
+
+    #[cfg(feature = "write")]
+    /// Perform any arbitrary PATCH request and take ownership of deserializing the response.
+    /// These actions typically require an API key.
+    pub fn patch(&self, route: &str, body: &str) -> Result<String, ClientError> {
+        self.send_write(reqwest::Method::PATCH, route, Some(body))
+    }
+
+    #[cfg(feature = "write")]
+    /// Perform any arbitrary DELETE request and take ownership of deserializing the response.
+    /// These actions typically require an API key.
+    pub fn delete(&self, route: &str) -> Result<String, ClientError> {
+        self.send_write(reqwest::Method::DELETE, route, None)
+    }
+
+    /// Query HTTP GET `/api/v1/codes` and deserialize the response.
+    ///
+    /// This is useful if you need the code itself, and the meta-information.
+    /// All Optional fields will try to have values, unless they were not provided by the remote.
+    ///
+    /// If you only need the code and the expiry information, use `get_codes_slim` instead.
+    pub fn get_codes(&self) -> Result<Vec<Code>, ClientError> {
+        let response = self.get("/codes")?;
+        let (codes, _) = client::decode_codes(&response, client::DecodeMode::Strict)?;
+
+        Ok(client::mapping_full(codes))
+    }
+
+    /// Query HTTP GET `/api/v1/codes` and deserialize the response, returning a slim subset including only essential data.
+    ///
+    /// This is useful if you only need the code itself, and not the meta-information.
+    /// All Optional fields will be None.
+    ///
+    /// If you need the code and the meta-information, use `get_codes` instead.
+    pub fn get_codes_slim(&self) -> Result<Vec<Code>, ClientError> {
+        let response = self.get("/codes")?;
+        let (codes, _) = client::decode_codes(&response, client::DecodeMode::Strict)?;
+
+        Ok(client::mapping_slim(codes))
+    }
+
+    /// Query HTTP PUT `/api/v1/codes` and deserialize the response.
+    /// *This requires an API Key.*
+    ///
+    /// Insert a Code into the remote service.
+    #[cfg(feature = "write")]
+    pub fn insert_code(
+        &self,
+        insert_request: write::InsertCodeRequest,
+    ) -> Result<Option<i32>, ClientError> {
+        let payload = puts::RemoteInsertCodeRequest::from(insert_request);
+
+        let result = self.put(
+            "/codes",
+            &serde_json::to_string(&payload).map_err(|e| ClientError::Serde(e.into()))?,
+        )?;
+
+        Ok(client::parse_insert_id(&result))
+    }
+
+    /// Query HTTP PATCH `/api/v1/codes/{code}`.
+    /// *This requires an API Key.*
+    ///
+    /// Correct an existing code, only the fields that are set in the request are changed.
+    #[cfg(feature = "write")]
+    pub fn update_code(&self, update_request: write::UpdateCodeRequest) -> Result<(), ClientError> {
+        let route = client::code_route(&update_request.code);
+        let payload = puts::RemoteUpdateCodeRequest::from(update_request);
+
+        self.patch(
+            &route,
+            &serde_json::to_string(&payload).map_err(|e| ClientError::Serde(e.into()))?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Query HTTP POST `/api/v1/codes/{code}/expire`.
+    /// *This requires an API Key.*
+    ///
+    /// Mark a code as expired right away, e.g. because it no longer works in-game.
+    #[cfg(feature = "write")]
+    pub fn expire_code(&self, code: &ChestCode) -> Result<(), ClientError> {
+        self.post(&format!("{}/expire", client::code_route(code)), "{}")?;
+
+        Ok(())
+    }
+
+    /// Query HTTP DELETE `/api/v1/codes/{code}`.
+    /// *This requires an API Key.*
+    ///
+    /// Remove a code from the remote service, e.g. because it was never valid.
+    #[cfg(feature = "write")]
+    pub fn delete_code(&self, code: &ChestCode) -> Result<(), ClientError> {
+        self.delete(&client::code_route(code))?;
+
+        Ok(())
+    }
+
+    /// Handles the response from the remote service, checking for errors.
+    fn response(&self, response: reqwest::blocking::Response) -> Result<String, ClientError> {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().map_err(ClientError::Reqwest)?;
+
+        client::error_for_status(status, &headers, &body)?;
+
+        Ok(body)
+    }
+
+    pub fn default_client() -> reqwest::blocking::Client {
+        reqwest::blocking::Client::builder()
+            .user_agent(client::CodesClient::user_agent())
+            .default_headers(client::CodesClient::default_headers())
+            .build()
+            .unwrap_or_else(|_| reqwest::blocking::Client::new())
+    }
+}
+
+impl Default for CodesClient {
+    fn default() -> Self {
+        Self::new_full(None, None, None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_construct_client_default() {
+        let client = CodesClient::default();
+        assert!(client.base_url.eq(DEFAULT_BASE_URL));
+        assert!(client.api_key.is_none());
+    }
+
+    #[test]
+    fn test_construct_client_with_base_url() {
+        assert_eq!(
+            CodesClient::new_full(None, Some("http://foo.example".to_string()), None).base_url,
+            "http://foo.example"
+        );
+    }
+
+    #[test]
+    fn test_client_url() {
+        let client = CodesClient::default();
+
+        assert_eq!(client.url("foo"), format!("{}/foo", DEFAULT_BASE_URL));
+        assert_eq!(client.url("/foo"), format!("{}/foo", DEFAULT_BASE_URL));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn test_put_requires_api_key() {
+        let client = CodesClient::default();
+
+        assert!(matches!(
+            client.put("/codes", "{}"),
+            Err(ClientError::ApiKeyMissing)
+        ));
+        assert!(matches!(
+            client.delete_code(&"FOOB-BARS-TEST".parse().unwrap()),
+            Err(ClientError::ApiKeyMissing)
+        ));
+    }
+}
@@ -0,0 +1,215 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::str::FromStr;
+
+/// Symbols that may appear in a code besides ASCII letters and digits.
+const ALLOWED_SYMBOLS: &[char] = &['!', '@', '#', '$', '%', '^', '&', '*'];
+
+/// ChestCode is a validated code that can be redeemed in Idle Champions of the Forgotten Realms.
+///
+/// Codes are 12 or 16 characters long, consisting of letters, digits and a few symbols (`!@#$%^&*`).
+/// Parsing is lenient: letters are uppercased and dashes may appear anywhere,
+/// the code is always stored in its canonical form of dash separated groups of four characters.
+///
+/// ```
+/// use licc::ChestCode;
+///
+/// let code: ChestCode = "foobbarstest".parse().unwrap();
+/// assert_eq!(code.as_str(), "FOOB-BARS-TEST");
+/// assert_eq!(code.raw(), "FOOBBARSTEST");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChestCode(String);
+
+/// The reason a string is not a valid `ChestCode`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseChestCodeError {
+    /// The code, without dashes, is not 12 or 16 characters long
+    InvalidLength(usize),
+    /// The code contains a character that never appears in codes
+    InvalidCharacter(char),
+}
+
+impl ChestCode {
+    /// The canonical form of the code, e.g. `FOOB-BARS-TEST`.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The code without dashes, e.g. `FOOBBARSTEST`.
+    pub fn raw(&self) -> String {
+        self.0.replace('-', "")
+    }
+}
+
+impl FromStr for ChestCode {
+    type Err = ParseChestCodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = Vec::with_capacity(16);
+
+        for c in s.chars().filter(|c| *c != '-') {
+            if !c.is_ascii_alphanumeric() && !ALLOWED_SYMBOLS.contains(&c) {
+                return Err(ParseChestCodeError::InvalidCharacter(c));
+            }
+            chars.push(c.to_ascii_uppercase());
+        }
+
+        if chars.len() != 12 && chars.len() != 16 {
+            return Err(ParseChestCodeError::InvalidLength(chars.len()));
+        }
+
+        let formatted = chars
+            .chunks(4)
+            .map(|group| group.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join("-");
+
+        Ok(Self(formatted))
+    }
+}
+
+impl TryFrom<String> for ChestCode {
+    type Error = ParseChestCodeError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl TryFrom<&str> for ChestCode {
+    type Error = ParseChestCodeError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for ChestCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for ChestCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for ChestCode {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for ChestCode {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for ChestCode {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl serde::Serialize for ChestCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ChestCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for ParseChestCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(
+                f,
+                "a code must be 12 or 16 characters long, excluding dashes, got {}",
+                len
+            ),
+            Self::InvalidCharacter(c) => write!(f, "a code cannot contain {:?}", c),
+        }
+    }
+}
+
+impl std::error::Error for ParseChestCodeError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_canonical() {
+        let code: ChestCode = "FOOB-BARS-TEST".parse().unwrap();
+        assert_eq!(code.as_str(), "FOOB-BARS-TEST");
+        assert_eq!(code.raw(), "FOOBBARSTEST");
+        assert_eq!(code.to_string(), "FOOB-BARS-TEST");
+    }
+
+    #[test]
+    fn test_parse_normalizes() {
+        for input in [
+            "foob-bars-test",
+            "FOOBBARSTEST",
+            "FO-OBBA-RST-EST",
+            "-foobbarstest-",
+        ] {
+            assert_eq!(input.parse::<ChestCode>().unwrap(), "FOOB-BARS-TEST");
+        }
+
+        assert_eq!(
+            "abcd1234efgh5678".parse::<ChestCode>().unwrap(),
+            "ABCD-1234-EFGH-5678"
+        );
+        assert_eq!(
+            "L0V3-Y0UR-D1C3-!#$*".parse::<ChestCode>().unwrap(),
+            "L0V3-Y0UR-D1C3-!#$*"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid() {
+        assert_eq!(
+            "FOOB-BARS".parse::<ChestCode>(),
+            Err(ParseChestCodeError::InvalidLength(8))
+        );
+        assert_eq!(
+            "FOOB-BARS-TESTS".parse::<ChestCode>(),
+            Err(ParseChestCodeError::InvalidLength(13))
+        );
+        assert_eq!(
+            "FOOB BARS TEST".parse::<ChestCode>(),
+            Err(ParseChestCodeError::InvalidCharacter(' '))
+        );
+        assert_eq!(
+            "FÖOB-BARS-TEST".parse::<ChestCode>(),
+            Err(ParseChestCodeError::InvalidCharacter('Ö'))
+        );
+    }
+
+    #[test]
+    fn test_serde() {
+        let code: ChestCode = serde_json::from_str(r#""foob-bars-test""#).unwrap();
+        assert_eq!(code, "FOOB-BARS-TEST");
+        assert_eq!(serde_json::to_string(&code).unwrap(), r#""FOOB-BARS-TEST""#);
+
+        assert!(serde_json::from_str::<ChestCode>(r#""foo""#).is_err());
+    }
+
+    #[test]
+    fn test_borrow_as_str_key() {
+        let mut codes = std::collections::HashSet::new();
+        codes.insert("FOOBBARSTEST".parse::<ChestCode>().unwrap());
+
+        assert!(codes.contains("FOOB-BARS-TEST"));
+    }
+}
@@ -2,34 +2,339 @@ use crate::api_key::ApiKey;
 use crate::client::error::{ClientError, ErrorResponse};
 #[cfg(feature = "write")]
 use crate::write;
-use crate::{Code, Source};
+#[cfg(feature = "write")]
+use crate::ChestCode;
+use crate::{Code, Source, SourceIndex};
 use reqwest;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+mod auth;
+#[cfg(feature = "write")]
+mod batch;
+mod builder;
+mod cache;
+mod decode;
+mod hooks;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod mirror;
+mod query;
+mod rate_limit;
+mod retry;
+mod server_info;
+
+#[cfg(feature = "signing")]
+pub use auth::HmacSigner;
+pub use auth::{ApiKeyHeader, Authenticator, BearerToken};
+#[cfg(feature = "write")]
+pub use batch::InsertReport;
+pub use builder::{CodesClientBuilder, TlsBackend};
+pub use cache::{CachedResponse, Fetched, FileCache, MemoryCache, ResponseCache};
+#[cfg(feature = "blocking")]
+pub(crate) use decode::decode_codes;
+pub use decode::{DecodeMode, DecodeWarning};
+pub use hooks::{Hook, RequestInfo, ResponseInfo};
+#[cfg(feature = "metrics")]
+pub use metrics::{Histogram, Metrics, MetricsSnapshot, RequestLabels};
+pub use mirror::{Merged, MirrorStatus, MirroredClient, Served};
+pub use query::{CodesPage, CodesQuery};
+pub use rate_limit::RateLimiter;
+pub use retry::RetryPolicy;
+pub use server_info::{Capability, ServerInfo};
 
 /// The default base URL
 /// This points to the service hosted by the author of this crate.
-static DEFAULT_BASE_URL: &str = "https://codes.idlechampions.liefland.net/v1";
+pub(crate) static DEFAULT_BASE_URL: &str = "https://codes.idlechampions.liefland.net/v1";
+
+/// How often a single request waits and tries again after being rate limited by the remote.
+const MAX_RATE_LIMITED_WAITS: u32 = 3;
 
+/// Clones share the underlying connection pool, `RateLimiter` and `ResponseCache`.
+#[derive(Clone)]
 pub struct CodesClient {
     base_url: String,
     #[allow(dead_code)]
     api_key: Option<ApiKey>,
     client: reqwest::Client,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
+    rate_limited_wait: Option<Duration>,
+    cache: Option<Arc<dyn ResponseCache>>,
+    decode_mode: DecodeMode,
+    hooks: Vec<Arc<dyn Hook>>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Metrics>,
+    /// How write requests are authenticated, sending the API key in `X-Api-Key` if None
+    #[cfg(feature = "write")]
+    authenticator: Option<Arc<dyn Authenticator>>,
+    /// What the remote told about itself, shared between clones so it is queried once.
+    server_info: Arc<OnceLock<ServerInfo>>,
+    /// Whether the remote has a batch endpoint for inserting codes,
+    /// None until we tried or the remote told us through `server_info`.
+    /// Shared between clones, so the endpoint is probed once.
+    #[cfg(feature = "write")]
+    batch_insert: Arc<OnceLock<bool>>,
+}
+
+/// A successful response of the remote, or a 304 Not Modified response to a conditional request.
+struct RawResponse {
+    status: reqwest::StatusCode,
+    headers: reqwest::header::HeaderMap,
+    body: String,
+}
+
+impl RawResponse {
+    fn header(&self, name: reqwest::header::HeaderName) -> Option<String> {
+        self.headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    }
 }
 
 pub mod error {
+    use reqwest::StatusCode;
+    use std::fmt;
+
     /// Any error that can happen during a request
     #[derive(Debug)]
     pub enum ClientError {
         /// Reqwest error
         Reqwest(reqwest::Error),
         /// Request failed to serialize or Response failed to deserialize
-        Serde(serde_json::Error),
+        Serde(SerdeError),
         /// The remote has returned a non-successful HTTP status code
         ServerError(ErrorResponse),
         /// You are attempting to make a write request without an API Key
         #[cfg(feature = "write")]
         ApiKeyMissing,
+        /// The base URL passed to the `CodesClientBuilder` is not usable
+        InvalidBaseUrl { url: String, reason: &'static str },
+        /// The rate passed to `CodesClientBuilder::rate_limit` is not a positive, finite number
+        InvalidRateLimit { requests_per_second: f64 },
+        /// The request failed on every attempt allowed by the `RetryPolicy`, `last` is the final error
+        RetriesExhausted {
+            attempts: u32,
+            last: Box<ClientError>,
+        },
+        /// The remote responded with 429 Too Many Requests.
+        /// `retry_after` is how long the remote asked us to wait, if it said so.
+        RateLimited {
+            retry_after: Option<std::time::Duration>,
+        },
+        /// The remote has returned a non-successful HTTP status code without a JSON error description,
+        /// e.g. an HTML page from a proxy or an empty 502.
+        UnexpectedResponse { status: StatusCode, body: String },
+        /// The `Authenticator` could not add credentials to the request
+        Authentication { reason: &'static str },
+        /// The remote reported, through `CodesClient::server_info`, that it does not support this
+        Unsupported { capability: super::Capability },
+    }
+
+    impl ClientError {
+        /// The HTTP status code the remote responded with, if it responded at all.
+        /// For `RetriesExhausted` this is the status code of the last attempt.
+        pub fn status_code(&self) -> Option<StatusCode> {
+            match self {
+                Self::Reqwest(err) => err.status(),
+                Self::ServerError(err) => u16::try_from(err.error.code)
+                    .ok()
+                    .and_then(|code| StatusCode::from_u16(code).ok()),
+                Self::UnexpectedResponse { status, .. } => Some(*status),
+                Self::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS),
+                Self::RetriesExhausted { last, .. } => last.status_code(),
+                _ => None,
+            }
+        }
+
+        /// Whether the request timed out, either locally or at a gateway in front of the remote.
+        pub fn is_timeout(&self) -> bool {
+            match self {
+                Self::Reqwest(err) if err.is_timeout() => true,
+                Self::RetriesExhausted { last, .. } => last.is_timeout(),
+                _ => matches!(
+                    self.status_code(),
+                    Some(StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT)
+                ),
+            }
+        }
+
+        /// Whether the remote responded with 409 Conflict, e.g. because a code already exists.
+        pub fn is_conflict(&self) -> bool {
+            self.status_code() == Some(StatusCode::CONFLICT)
+        }
+
+        /// Whether the remote responded with 404 Not Found.
+        pub fn is_not_found(&self) -> bool {
+            self.status_code() == Some(StatusCode::NOT_FOUND)
+        }
+
+        /// Whether the remote refused the API key, or no API key was sent (401 or 403).
+        pub fn is_unauthorized(&self) -> bool {
+            matches!(
+                self.status_code(),
+                Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+            )
+        }
+
+        /// Whether the failure is likely transient, and the request worth attempting again later.
+        /// This includes being rate limited.
+        pub fn is_retryable(&self) -> bool {
+            match self {
+                Self::Reqwest(err) if err.is_connect() || err.is_timeout() => true,
+                Self::RetriesExhausted { last, .. } => last.is_retryable(),
+                _ => matches!(
+                    self.status_code(),
+                    Some(
+                        StatusCode::REQUEST_TIMEOUT
+                            | StatusCode::TOO_MANY_REQUESTS
+                            | StatusCode::INTERNAL_SERVER_ERROR
+                            | StatusCode::BAD_GATEWAY
+                            | StatusCode::SERVICE_UNAVAILABLE
+                            | StatusCode::GATEWAY_TIMEOUT
+                    )
+                ),
+            }
+        }
+    }
+
+    impl fmt::Display for ClientError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Reqwest(_) => f.write_str("the request to the remote failed"),
+                Self::Serde(_) => f.write_str("failed to (de)serialize JSON"),
+                Self::ServerError(err) => write!(
+                    f,
+                    "the remote returned an error ({}): {}",
+                    err.error.code, err.error.description
+                ),
+                #[cfg(feature = "write")]
+                Self::ApiKeyMissing => f.write_str("an API key is required for this request"),
+                Self::InvalidBaseUrl { url, reason } => {
+                    write!(f, "invalid base URL {:?}: {}", url, reason)
+                }
+                Self::InvalidRateLimit {
+                    requests_per_second,
+                } => write!(
+                    f,
+                    "invalid rate limit of {} requests per second, must be a positive, finite number",
+                    requests_per_second
+                ),
+                Self::RetriesExhausted { attempts, .. } => {
+                    write!(f, "the request failed after {} attempts", attempts)
+                }
+                Self::RateLimited {
+                    retry_after: Some(retry_after),
+                } => write!(
+                    f,
+                    "rate limited by the remote, retry after {}s",
+                    retry_after.as_secs()
+                ),
+                Self::RateLimited { retry_after: None } => {
+                    f.write_str("rate limited by the remote")
+                }
+                Self::UnexpectedResponse { status, .. } => {
+                    write!(f, "the remote returned an unexpected response ({})", status)
+                }
+                Self::Authentication { reason } => {
+                    write!(f, "failed to authenticate the request: {}", reason)
+                }
+                Self::Unsupported { capability } => {
+                    write!(f, "the remote does not support {}", capability)
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for ClientError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                Self::Reqwest(err) => Some(err),
+                Self::Serde(err) => Some(err),
+                Self::RetriesExhausted { last, .. } => Some(last.as_ref()),
+                _ => None,
+            }
+        }
+    }
+
+    /// How much of the body to include in a `SerdeError`
+    const SNIPPET_LEN: usize = 120;
+
+    /// A JSON (de)serialization error.
+    /// When a response failed to deserialize, it includes where in the response body it went wrong.
+    #[derive(Debug)]
+    pub struct SerdeError {
+        pub source: serde_json::Error,
+        /// The JSON path of the offending value, e.g. `codes[3].expired`
+        pub path: Option<String>,
+        /// The part of the response body around the offending value
+        pub snippet: Option<String>,
+    }
+
+    impl SerdeError {
+        pub(crate) fn new(
+            source: serde_json::Error,
+            path: Option<String>,
+            body: Option<&str>,
+        ) -> Self {
+            let snippet = body.map(|body| snippet(body, source.line(), source.column()));
+            let path = path.filter(|path| path != ".");
+
+            Self {
+                source,
+                path,
+                snippet,
+            }
+        }
+    }
+
+    impl fmt::Display for SerdeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match &self.path {
+                Some(path) => write!(f, "invalid JSON at `{}`", path)?,
+                None => f.write_str("invalid JSON")?,
+            }
+            match &self.snippet {
+                Some(snippet) => write!(f, " near `{}`", snippet),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl std::error::Error for SerdeError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.source)
+        }
+    }
+
+    impl From<serde_json::Error> for SerdeError {
+        fn from(source: serde_json::Error) -> Self {
+            Self::new(source, None, None)
+        }
+    }
+
+    /// Up to `SNIPPET_LEN` characters of `body` around the given 1-based line and column,
+    /// or the start of the body if the position is unknown (line 0).
+    fn snippet(body: &str, line: usize, column: usize) -> String {
+        let (text, center) = match line {
+            0 => (body, 0),
+            line => (
+                body.lines().nth(line - 1).unwrap_or(body),
+                column.saturating_sub(1),
+            ),
+        };
+
+        let chars: Vec<char> = text.chars().collect();
+        let end = (center.saturating_sub(SNIPPET_LEN / 2) + SNIPPET_LEN).min(chars.len());
+        let start = end.saturating_sub(SNIPPET_LEN);
+
+        chars[start..end]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string()
     }
 
     /// ErrorResponse is returned from the remote when an error occurs.
@@ -53,8 +358,8 @@ pub mod error {
 }
 
 #[derive(serde::Deserialize)]
-struct RetrieveCodesCodeResponse {
-    code: String,
+pub(crate) struct RetrieveCodesCodeResponse {
+    code: decode::ListedCode,
     expired: bool,
     expires_at: String,
     sources: SourcesMapping,
@@ -68,18 +373,27 @@ struct SourcesMapping {
 }
 
 #[derive(serde::Deserialize)]
-struct RetrieveCodesResponse {
+pub(crate) struct RetrieveCodesResponse {
     codes: Vec<RetrieveCodesCodeResponse>,
     sources: HashMap<i32, Source>,
+    /// Set by remotes that paginate, if there is another page
+    #[serde(default)]
+    next_cursor: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub(crate) struct RetrieveSourcesResponse {
+    sources: HashMap<i32, Source>,
 }
 
 #[cfg(feature = "write")]
-mod puts {
-    use crate::write::InsertCodeRequest;
+pub(crate) mod puts {
+    use crate::write::{InsertCodeRequest, SourceLookup, UpdateCodeRequest, UpdateSourceRequest};
+    use crate::ChestCode;
 
     #[derive(Clone, Debug, serde::Serialize)]
     pub(crate) struct RemoteInsertCodeRequest {
-        code: String,
+        code: ChestCode,
         expires_at: u64,
         creator_name: String,
         creator_url: String,
@@ -104,6 +418,81 @@ mod puts {
             }
         }
     }
+
+    #[derive(Clone, Debug, serde::Serialize)]
+    pub(crate) struct RemoteUpdateCodeRequest {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expires_at: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expired: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        creator_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        creator_url: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        submitter_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        submitter_url: Option<String>,
+    }
+
+    impl From<UpdateCodeRequest> for RemoteUpdateCodeRequest {
+        fn from(value: UpdateCodeRequest) -> Self {
+            let (creator_name, creator_url) = match value.creator {
+                None => (None, None),
+                Some(s) => (Some(s.name), Some(s.url)),
+            };
+            let (submitter_name, submitter_url) = match value.submitter {
+                None => (None, None),
+                Some(s) => (Some(s.name), Some(s.url)),
+            };
+
+            Self {
+                expires_at: value.expires_at,
+                expired: value.expired,
+                creator_name,
+                creator_url,
+                submitter_name,
+                submitter_url,
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, serde::Serialize)]
+    pub(crate) struct RemoteSourceRequest {
+        name: String,
+        url: String,
+    }
+
+    impl From<SourceLookup> for RemoteSourceRequest {
+        fn from(value: SourceLookup) -> Self {
+            Self {
+                name: value.name,
+                url: value.url,
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, serde::Serialize)]
+    pub(crate) struct RemoteUpdateSourceRequest {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    }
+
+    impl From<UpdateSourceRequest> for RemoteUpdateSourceRequest {
+        fn from(value: UpdateSourceRequest) -> Self {
+            Self {
+                name: value.name,
+                url: value.url,
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, serde::Serialize)]
+    pub(crate) struct RemoteMergeSourcesRequest {
+        pub(crate) sources: Vec<i32>,
+    }
 }
 
 impl CodesClient {
@@ -117,6 +506,8 @@ impl CodesClient {
     /// Construct a new CodesClient, optionally providing an API Key.
     /// If left to None, default values will be used.
     /// If no values need to change, consider using `default` instead.
+    ///
+    /// The base URL is used as-is, use `builder` to have it validated and for more options.
     pub fn new_full(
         api_key: Option<ApiKey>,
         base_url: Option<String>,
@@ -126,9 +517,32 @@ impl CodesClient {
             base_url: base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
             client: client.unwrap_or_else(Self::default_client),
             api_key,
+            retry_policy: RetryPolicy::none(),
+            rate_limiter: None,
+            rate_limited_wait: None,
+            cache: None,
+            decode_mode: DecodeMode::Strict,
+            hooks: Vec::new(),
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "write")]
+            authenticator: None,
+            server_info: Arc::default(),
+            #[cfg(feature = "write")]
+            batch_insert: Arc::default(),
         }
     }
 
+    /// Create a `CodesClientBuilder` to configure the client in more detail.
+    pub fn builder() -> CodesClientBuilder {
+        CodesClientBuilder::new()
+    }
+
+    /// The base URL requests are sent to, e.g. `https://codes.idlechampions.liefland.net/v1`.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
     fn url(&self, path: &str) -> String {
         if path.starts_with('/') {
             return format!("{}{}", self.base_url, path);
@@ -138,40 +552,172 @@ impl CodesClient {
 
     /// Perform any arbitrary GET request and take ownership of deserializing the response.
     pub async fn get(&self, route: &str) -> Result<String, ClientError> {
-        let response = self
+        self.fetch(route).await.map_err(|err| self.failed(err))
+    }
+
+    /// Like `get`, but leaves recording the error to the caller, which may handle it instead.
+    async fn fetch(&self, route: &str) -> Result<String, ClientError> {
+        let request = self
             .client
             .get(self.url(route))
             .header("Accept", "application/json")
-            .header("Content-Type", "application/json")
-            .send()
+            .header("Content-Type", "application/json");
+
+        Ok(self.send(request, true, None).await?.body)
+    }
+
+    /// Perform a GET request, revalidating a previously cached response if a `ResponseCache` is configured.
+    async fn get_cached(&self, route: &str) -> Result<Fetched<String>, ClientError> {
+        let url = self.url(route);
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => {
+                return Ok(Fetched {
+                    value: self.get(route).await?,
+                    from_cache: false,
+                    warnings: Vec::new(),
+                })
+            }
+        };
+
+        let cached = cache.get(&url);
+        let mut request = self
+            .client
+            .get(&url)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json");
+
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = self
+            .send(request, true, None)
+            .await
+            .map_err(|err| self.failed(err))?;
+
+        if response.status == reqwest::StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                #[cfg(feature = "metrics")]
+                self.metrics(|metrics| metrics.record_cache(true));
+
+                return Ok(Fetched {
+                    value: cached.body,
+                    from_cache: true,
+                    warnings: Vec::new(),
+                });
+            }
+        }
+
+        let fresh = CachedResponse {
+            etag: response.header(reqwest::header::ETAG),
+            last_modified: response.header(reqwest::header::LAST_MODIFIED),
+            body: response.body,
+        };
+
+        #[cfg(feature = "metrics")]
+        self.metrics(|metrics| metrics.record_cache(false));
+
+        if fresh.is_revalidatable() {
+            cache.put(&url, fresh.clone());
+        }
+
+        Ok(Fetched {
+            value: fresh.body,
+            from_cache: false,
+            warnings: Vec::new(),
+        })
+    }
+
+    /// Sends a request that modifies the remote, which requires an API key or `Authenticator`.
+    #[cfg(feature = "write")]
+    async fn send_write(
+        &self,
+        method: reqwest::Method,
+        route: &str,
+        body: Option<&str>,
+        idempotent: bool,
+    ) -> Result<RawResponse, ClientError> {
+        let authenticator = self.authenticator()?;
+
+        let request = self
+            .client
+            .request(method, self.url(route))
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json");
+        let request = match body {
+            Some(body) => request.body(body.to_string()),
+            None => request,
+        };
+
+        self.send(request, idempotent, Some(authenticator.as_ref()))
             .await
-            .map_err(ClientError::Reqwest)?;
+    }
+
+    /// The configured `Authenticator`, or one sending the API key.
+    #[cfg(feature = "write")]
+    fn authenticator(&self) -> Result<Arc<dyn Authenticator>, ClientError> {
+        if let Some(authenticator) = &self.authenticator {
+            return Ok(authenticator.clone());
+        }
 
-        self.response(response).await
+        match &self.api_key {
+            Some(api_key) => Ok(Arc::new(ApiKeyHeader(api_key.clone()))),
+            None => Err(ClientError::ApiKeyMissing),
+        }
     }
 
     #[cfg(feature = "write")]
     /// Perform any arbitrary PUT request and take ownership of deserializing the response.
     /// These actions typically require an API key.
-    pub async fn put(&mut self, route: &str, body: &str) -> Result<String, ClientError> {
-        let api_key = self
-            .api_key
-            .as_ref()
-            .ok_or(ClientError::ApiKeyMissing)?
-            .get();
+    pub async fn put(&self, route: &str, body: &str) -> Result<String, ClientError> {
+        let response = self
+            .send_write(reqwest::Method::PUT, route, Some(body), false)
+            .await
+            .map_err(|err| self.failed(err))?;
 
+        Ok(response.body)
+    }
+
+    #[cfg(feature = "write")]
+    /// Perform any arbitrary POST request and take ownership of deserializing the response.
+    /// These actions typically require an API key.
+    pub async fn post(&self, route: &str, body: &str) -> Result<String, ClientError> {
         let response = self
-            .client
-            .put(self.url(route))
-            .header("Accept", "application/json")
-            .header("Content-Type", "application/json")
-            .header("X-Api-Key", api_key)
-            .body(body.to_string())
-            .send()
+            .send_write(reqwest::Method::POST, route, Some(body), false)
+            .await
+            .map_err(|err| self.failed(err))?;
+
+        Ok(response.body)
+    }
+
+    #[cfg(feature = "write")]
+    /// Perform any arbitrary PATCH request and take ownership of deserializing the response.
+    /// These actions typically require an API key.
+    pub async fn patch(&self, route: &str, body: &str) -> Result<String, ClientError> {
+        let response = self
+            .send_write(reqwest::Method::PATCH, route, Some(body), false)
             .await
-            .map_err(ClientError::Reqwest)?;
+            .map_err(|err| self.failed(err))?;
 
-        self.response(response).await
+        Ok(response.body)
+    }
+
+    #[cfg(feature = "write")]
+    /// Perform any arbitrary DELETE request and take ownership of deserializing the response.
+    /// These actions typically require an API key.
+    pub async fn delete(&self, route: &str) -> Result<String, ClientError> {
+        let response = self
+            .send_write(reqwest::Method::DELETE, route, None, true)
+            .await
+            .map_err(|err| self.failed(err))?;
+
+        Ok(response.body)
     }
 
     /// Query HTTP GET `/api/v1/codes` and deserialize the response.
@@ -181,11 +727,39 @@ impl CodesClient {
     ///
     /// If you only need the code and the expiry information, use `get_codes_slim` instead.
     pub async fn get_codes(&self) -> Result<Vec<Code>, ClientError> {
-        let response = self.get("/codes").await?;
+        Ok(self.fetch_codes().await?.value)
+    }
+
+    /// Like `get_codes`, but also tells whether the codes were served from the `ResponseCache`
+    /// because the remote reported that they did not change since the previous request,
+    /// and which entries were skipped when decoding in `DecodeMode::Lenient`.
+    pub async fn fetch_codes(&self) -> Result<Fetched<Vec<Code>>, ClientError> {
+        let response = self.get_cached("/codes").await?;
 
-        let codes: RetrieveCodesResponse = serde_json::from_str(&response).unwrap();
+        let (codes, warnings) = decode::decode_codes(&response.value, self.decode_mode)
+            .map_err(|err| self.failed(err))?;
 
-        Ok(mapping_full(codes))
+        #[cfg(feature = "metrics")]
+        self.metrics(|metrics| metrics.record_codes(codes.codes.len()));
+
+        Ok(Fetched {
+            value: mapping_full(codes),
+            from_cache: response.from_cache,
+            warnings,
+        })
+    }
+
+    /// Like `get_codes`, but also returns the deduplicated table of sources the codes refer to.
+    pub async fn get_codes_with_sources(&self) -> Result<(Vec<Code>, SourceIndex), ClientError> {
+        let response = self.get_cached("/codes").await?.value;
+
+        let (codes, _) =
+            decode::decode_codes(&response, self.decode_mode).map_err(|err| self.failed(err))?;
+
+        #[cfg(feature = "metrics")]
+        self.metrics(|metrics| metrics.record_codes(codes.codes.len()));
+
+        Ok(mapping_with_sources(codes))
     }
 
     /// Query HTTP GET `/api/v1/codes` and deserialize the response, returning a slim subset including only essential data.
@@ -195,20 +769,187 @@ impl CodesClient {
     ///
     /// If you need the code and the meta-information, use `get_codes` instead.
     pub async fn get_codes_slim(&self) -> Result<Vec<Code>, ClientError> {
-        let response = self.get("/codes").await?;
+        let response = self.get_cached("/codes").await?.value;
+
+        let (codes, _) =
+            decode::decode_codes(&response, self.decode_mode).map_err(|err| self.failed(err))?;
 
-        let codes: RetrieveCodesResponse = serde_json::from_str(&response).unwrap();
+        #[cfg(feature = "metrics")]
+        self.metrics(|metrics| metrics.record_codes(codes.codes.len()));
 
         Ok(mapping_slim(codes))
     }
 
+    /// Query HTTP GET `/api/v1/codes` for one page of the codes matching `query`.
+    ///
+    /// Filters and pagination the remote does not support, according to `server_info`, are applied by the client,
+    /// which then downloads the full list of codes. The same happens if `server_info` fails.
+    pub async fn query_codes(&self, query: &CodesQuery) -> Result<CodesPage, ClientError> {
+        let support = self.query_support(query).await;
+
+        self.query_page(query, support, false).await
+    }
+
+    /// Like `query_codes`, but follows the pages and yields every matching code.
+    ///
+    /// The `limit` of the query is the size of the pages requested from the remote, not the total number of codes,
+    /// see `StreamExt::take` for that. When the client applies the query, all codes are taken from a single download.
+    pub fn query_codes_stream(
+        &self,
+        query: CodesQuery,
+    ) -> impl futures_util::Stream<Item = Result<Code, ClientError>> + '_ {
+        let state = (Some(query), VecDeque::new(), None);
+
+        futures_util::stream::unfold(
+            state,
+            move |(mut next, mut buffer, mut support)| async move {
+                loop {
+                    if let Some(code) = buffer.pop_front() {
+                        return Some((Ok(code), (next, buffer, support)));
+                    }
+
+                    let query = next?;
+                    // Looked up once, the pages of a query are all applied the same way.
+                    let page_support = match support {
+                        Some(support) => support,
+                        None => self.query_support(&query).await,
+                    };
+                    support = Some(page_support);
+
+                    match self.query_page(&query, page_support, true).await {
+                        // An empty page ends the results, even if the remote links to another one.
+                        Ok(page) if page.codes.is_empty() => return None,
+                        Ok(page) => {
+                            buffer.extend(page.codes);
+                            next = page.next;
+                        }
+                        Err(err) => return Some((Err(err), (None, buffer, support))),
+                    }
+                }
+            },
+        )
+    }
+
+    /// Whether the remote applies the filters of `query`, and whether it paginates, according to `server_info`.
+    /// If the remote's capabilities cannot be discovered, the client applies the query itself.
+    async fn query_support(&self, query: &CodesQuery) -> (bool, bool) {
+        let info = self.discover().await;
+        #[cfg(feature = "tracing")]
+        if let Err(err) = &info {
+            tracing::debug!(error = %err, "server info unavailable, applying the query on the client");
+        }
+        let Ok(info) = info else {
+            return (false, false);
+        };
+        let filter = info.supports(Capability::Filtering) == Some(true);
+        // The client cannot filter pages of the remote, so the remote only paginates if it also filters.
+        let paginate =
+            info.supports(Capability::Pagination) == Some(true) && (filter || !query.has_filters());
+
+        (filter, paginate)
+    }
+
+    /// Queries one page of codes. When streaming from a remote that does not paginate,
+    /// all remaining codes are returned as one page instead of downloading the full list for every page of `limit` codes.
+    async fn query_page(
+        &self,
+        query: &CodesQuery,
+        (filter, paginate): (bool, bool),
+        stream: bool,
+    ) -> Result<CodesPage, ClientError> {
+        let query_string = query.query_string(filter, paginate);
+        let route = match query_string.is_empty() {
+            true => "/codes".to_string(),
+            false => format!("/codes?{}", query_string),
+        };
+
+        let response = self.get_cached(&route).await?.value;
+        let (codes, _) =
+            decode::decode_codes(&response, self.decode_mode).map_err(|err| self.failed(err))?;
+        let next_cursor = codes.next_cursor.clone();
+        let codes = mapping_full(codes);
+
+        #[cfg(feature = "metrics")]
+        self.metrics(|metrics| metrics.record_codes(codes.len()));
+
+        if paginate {
+            let next = query.next_page(codes.len(), next_cursor);
+            return Ok(CodesPage { codes, next });
+        }
+
+        match stream {
+            true => {
+                let mut query = query.clone();
+                query.limit = None;
+                Ok(query.apply(codes))
+            }
+            false => Ok(query.apply(codes)),
+        }
+    }
+
+    /// Query HTTP GET `/api/v1/sources` and deserialize the response.
+    ///
+    /// This includes sources that no listed code refers to, unlike `get_codes_with_sources`.
+    pub async fn get_sources(&self) -> Result<SourceIndex, ClientError> {
+        let response = self.get("/sources").await?;
+
+        let sources: RetrieveSourcesResponse =
+            decode::decode(&response).map_err(|err| self.failed(err))?;
+
+        Ok(SourceIndex::from(sources.sources))
+    }
+
+    /// Query HTTP GET `/api/v1/sources/{id}` and deserialize the response.
+    /// Returns None if the remote does not know the source.
+    pub async fn get_source(&self, id: i32) -> Result<Option<Source>, ClientError> {
+        match self.fetch(&format!("/sources/{}", id)).await {
+            Ok(response) => Ok(Some(
+                decode::decode(&response).map_err(|err| self.failed(err))?,
+            )),
+            Err(err) if err.is_not_found() => Ok(None),
+            Err(err) => Err(self.failed(err)),
+        }
+    }
+
+    /// Query HTTP GET `/api/v1/info` to discover the version and optional features of the remote.
+    ///
+    /// The result is cached, and shared between clones of the client.
+    /// Remotes without an info endpoint are reported with `discovered` set to false.
+    pub async fn server_info(&self) -> Result<ServerInfo, ClientError> {
+        self.discover().await.map_err(|err| self.failed(err))
+    }
+
+    /// Like `server_info`, but leaves recording the error to the caller, which may handle it instead.
+    async fn discover(&self) -> Result<ServerInfo, ClientError> {
+        if let Some(info) = self.server_info.get() {
+            return Ok(info.clone());
+        }
+
+        let info = match self.fetch("/info").await {
+            Ok(response) => decode::decode::<server_info::RemoteServerInfo>(&response)?.into(),
+            Err(err) if server_info::is_missing_endpoint(&err) => ServerInfo::default(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(self.server_info.get_or_init(|| info).clone())
+    }
+
+    /// Fails with `ClientError::Unsupported` if the remote reported that it does not support `capability`.
+    /// Remotes that did not report their features are assumed to support it.
+    pub async fn require(&self, capability: Capability) -> Result<(), ClientError> {
+        match self.server_info().await?.supports(capability) {
+            Some(false) => Err(self.failed(ClientError::Unsupported { capability })),
+            _ => Ok(()),
+        }
+    }
+
     /// Query HTTP PUT `/api/v1/codes` and deserialize the response.
     /// *This requires an API Key.*
     ///
     /// Insert a Code into the remote service.
     #[cfg(feature = "write")]
     pub async fn insert_code(
-        &mut self,
+        &self,
         insert_request: write::InsertCodeRequest,
     ) -> Result<Option<i32>, ClientError> {
         let payload = puts::RemoteInsertCodeRequest::from(insert_request);
@@ -216,32 +957,422 @@ impl CodesClient {
         let result = self
             .put(
                 "/codes",
-                &serde_json::to_string(&payload).map_err(ClientError::Serde)?,
+                &serde_json::to_string(&payload)
+                    .map_err(|e| self.failed(ClientError::Serde(e.into())))?,
+            )
+            .await?;
+
+        Ok(parse_insert_id(&result))
+    }
+
+    /// Insert many codes at once, e.g. all codes found in a single post.
+    /// *This requires an API Key.*
+    ///
+    /// Uses HTTP PUT `/api/v1/codes/batch` if the remote has it,
+    /// otherwise the codes are inserted one by one, a few at the same time.
+    /// Fails only if the batch as a whole failed, the result of each code is in the `InsertReport`.
+    #[cfg(feature = "write")]
+    pub async fn insert_codes(
+        &self,
+        insert_requests: impl IntoIterator<Item = write::InsertCodeRequest>,
+    ) -> Result<InsertReport, ClientError> {
+        use futures_util::StreamExt;
+
+        self.authenticator().map_err(|err| self.failed(err))?;
+
+        let insert_requests: Vec<write::InsertCodeRequest> = insert_requests.into_iter().collect();
+        if insert_requests.is_empty() {
+            return Ok(InsertReport {
+                results: Vec::new(),
+            });
+        }
+
+        if self.batch_insert.get().is_none() {
+            if let Some(supported) = self.server_info().await?.supports(Capability::BatchInsert) {
+                let _ = self.batch_insert.set(supported);
+            }
+        }
+
+        if self.batch_insert.get() != Some(&false) {
+            let codes = insert_requests.iter().map(|r| r.code.clone()).collect();
+            let payload = batch::RemoteBatchInsertRequest {
+                codes: insert_requests.iter().cloned().map(Into::into).collect(),
+            };
+
+            let body = serde_json::to_string(&payload)
+                .map_err(|e| self.failed(ClientError::Serde(e.into())))?;
+            let result = self
+                .send_write(reqwest::Method::PUT, "/codes/batch", Some(&body), false)
+                .await;
+
+            match result {
+                Ok(response) => {
+                    let _ = self.batch_insert.set(true);
+                    return batch::report(codes, &response.body).map_err(|err| self.failed(err));
+                }
+                Err(err) if batch::is_unsupported(&err) => {
+                    let _ = self.batch_insert.set(false);
+                }
+                Err(err) => return Err(self.failed(err)),
+            }
+        }
+
+        let results = futures_util::stream::iter(insert_requests)
+            .map(|insert_request| async move {
+                let code = insert_request.code.clone();
+                (code, self.insert_code(insert_request).await)
+            })
+            .buffered(batch::INSERT_CONCURRENCY)
+            .collect()
+            .await;
+
+        Ok(InsertReport { results })
+    }
+
+    /// Query HTTP PATCH `/api/v1/codes/{code}`.
+    /// *This requires an API Key.*
+    ///
+    /// Correct an existing code, only the fields that are set in the request are changed.
+    #[cfg(feature = "write")]
+    pub async fn update_code(
+        &self,
+        update_request: write::UpdateCodeRequest,
+    ) -> Result<(), ClientError> {
+        let route = code_route(&update_request.code);
+        let payload = puts::RemoteUpdateCodeRequest::from(update_request);
+
+        self.patch(
+            &route,
+            &serde_json::to_string(&payload)
+                .map_err(|e| self.failed(ClientError::Serde(e.into())))?,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Query HTTP POST `/api/v1/codes/{code}/expire`.
+    /// *This requires an API Key.*
+    ///
+    /// Mark a code as expired right away, e.g. because it no longer works in-game.
+    #[cfg(feature = "write")]
+    pub async fn expire_code(&self, code: &ChestCode) -> Result<(), ClientError> {
+        self.post(&format!("{}/expire", code_route(code)), "{}")
+            .await?;
+
+        Ok(())
+    }
+
+    /// Query HTTP DELETE `/api/v1/codes/{code}`.
+    /// *This requires an API Key.*
+    ///
+    /// Remove a code from the remote service, e.g. because it was never valid.
+    #[cfg(feature = "write")]
+    pub async fn delete_code(&self, code: &ChestCode) -> Result<(), ClientError> {
+        self.delete(&code_route(code)).await?;
+
+        Ok(())
+    }
+
+    /// Query HTTP PUT `/api/v1/sources` and deserialize the response.
+    /// *This requires an API Key.*
+    ///
+    /// Insert a Source into the remote service, returns its ID.
+    #[cfg(feature = "write")]
+    pub async fn create_source(
+        &self,
+        source: write::SourceLookup,
+    ) -> Result<Option<i32>, ClientError> {
+        let payload = puts::RemoteSourceRequest::from(source);
+
+        let result = self
+            .put(
+                "/sources",
+                &serde_json::to_string(&payload)
+                    .map_err(|e| self.failed(ClientError::Serde(e.into())))?,
             )
             .await?;
 
-        // Should always work, but perhaps the remote service has a different version
-        // and now has a changed response?
-        match result.parse::<i32>() {
-            Ok(id) => Ok(Some(id)),
-            Err(_) => Ok(None),
+        Ok(parse_insert_id(&result))
+    }
+
+    /// Query HTTP PATCH `/api/v1/sources/{id}`.
+    /// *This requires an API Key.*
+    ///
+    /// Correct the name or URL of an existing source.
+    #[cfg(feature = "write")]
+    pub async fn update_source(
+        &self,
+        update_request: write::UpdateSourceRequest,
+    ) -> Result<(), ClientError> {
+        let route = format!("/sources/{}", update_request.id);
+        let payload = puts::RemoteUpdateSourceRequest::from(update_request);
+
+        self.patch(
+            &route,
+            &serde_json::to_string(&payload)
+                .map_err(|e| self.failed(ClientError::Serde(e.into())))?,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Query HTTP POST `/api/v1/sources/{into}/merge`.
+    /// *This requires an API Key.*
+    ///
+    /// Merge duplicate sources into the source `into`, codes attributed to them are attributed to `into` instead.
+    #[cfg(feature = "write")]
+    pub async fn merge_sources(
+        &self,
+        into: i32,
+        sources: impl IntoIterator<Item = i32>,
+    ) -> Result<(), ClientError> {
+        self.require(Capability::SourceMerge).await?;
+
+        let payload = puts::RemoteMergeSourcesRequest {
+            sources: sources.into_iter().filter(|id| *id != into).collect(),
+        };
+
+        self.post(
+            &format!("/sources/{}/merge", into),
+            &serde_json::to_string(&payload)
+                .map_err(|e| self.failed(ClientError::Serde(e.into())))?,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sends the request, retrying transient failures as allowed by the `RetryPolicy`.
+    /// Requests that are not idempotent are only retried if the policy opts in to it.
+    ///
+    /// If the remote rate limits us and the client is configured to wait, the request is sent again
+    /// once the remote allows it. The remote did not process the request, so this is safe for any method.
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+        idempotent: bool,
+        authenticator: Option<&dyn Authenticator>,
+    ) -> Result<RawResponse, ClientError> {
+        let may_retry = self.retry_policy.allows(idempotent);
+        let mut attempt = 1;
+        let mut rate_limited_waits = 0;
+
+        loop {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire().await;
+            }
+
+            // Our request bodies are always in-memory strings, so cloning cannot fail.
+            let mut this_request = request
+                .try_clone()
+                .expect("request body should be cloneable")
+                .build()
+                .map_err(ClientError::Reqwest)?;
+
+            // Hooks run before authenticating, so that signatures cover any changes they make.
+            for hook in &self.hooks {
+                hook.on_request(&mut this_request, attempt);
+            }
+
+            // Authenticated again for every attempt, signatures may include a timestamp or nonce.
+            if let Some(authenticator) = authenticator {
+                authenticator.authenticate(&mut this_request)?;
+            }
+
+            let (result, retry_after) = self.attempt(this_request, attempt).await;
+
+            let err = match result {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+
+            if let ClientError::RateLimited { retry_after } = &err {
+                if let Some(wait) = self.rate_limited_wait(*retry_after, rate_limited_waits) {
+                    if let Some(rate_limiter) = &self.rate_limiter {
+                        rate_limiter.pause_for(wait);
+                    } else {
+                        tokio::time::sleep(wait).await;
+                    }
+                    rate_limited_waits += 1;
+                    #[cfg(feature = "metrics")]
+                    self.metrics(|metrics| metrics.record_rate_limit_wait());
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(wait_ms = wait.as_millis() as u64, "rate limited, waiting");
+                    continue;
+                }
+            }
+
+            let delay = match self.retry_policy.delay(attempt, retry_after) {
+                Some(delay)
+                    if may_retry
+                        && attempt < self.retry_policy.max_attempts()
+                        && retry::is_transient(&err) =>
+                {
+                    delay
+                }
+                _ => {
+                    let err = match attempt {
+                        1 => err,
+                        attempts => ClientError::RetriesExhausted {
+                            attempts,
+                            last: Box::new(err),
+                        },
+                    };
+
+                    return Err(err);
+                }
+            };
+            #[cfg(feature = "tracing")]
+            tracing::debug!(attempt, delay_ms = delay.as_millis() as u64, error = %err, "retrying");
+            #[cfg(feature = "metrics")]
+            self.metrics(|metrics| metrics.record_retry());
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// How long to wait before trying a rate limited request again,
+    /// or None if the client is not configured to wait this long (or this often).
+    fn rate_limited_wait(&self, retry_after: Option<Duration>, waits: u32) -> Option<Duration> {
+        let max_wait = self.rate_limited_wait?;
+        let wait = retry_after.unwrap_or(Duration::from_secs(1));
+
+        if waits >= MAX_RATE_LIMITED_WAITS || wait > max_wait {
+            return None;
+        }
+
+        Some(wait)
+    }
+
+    /// Sends a single attempt of a request through the hooks, in a tracing span if enabled.
+    /// Also returns how long the remote asked us to wait before trying again, if it did.
+    async fn attempt(
+        &self,
+        request: reqwest::Request,
+        attempt: u32,
+    ) -> (Result<RawResponse, ClientError>, Option<Duration>) {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "request",
+            method = %request.method(),
+            route = request.url().path(),
+            attempt,
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+            bytes = tracing::field::Empty,
+        );
+
+        let future = self.execute(request, attempt);
+
+        #[cfg(feature = "tracing")]
+        let future = tracing::Instrument::instrument(future, span);
+
+        future.await
+    }
+
+    async fn execute(
+        &self,
+        request: reqwest::Request,
+        attempt: u32,
+    ) -> (Result<RawResponse, ClientError>, Option<Duration>) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(headers = ?request.headers(), "sending request");
+
+        let mut info = RequestInfo::new(&request, attempt);
+        let started = Instant::now();
+        let mut retry_after = None;
+
+        let result = match self.client.execute(request).await {
+            Ok(response) => {
+                retry_after = retry::retry_after(response.headers());
+                self.response(response, &mut info, started).await
+            }
+            Err(err) => Err(ClientError::Reqwest(err)),
+        };
+
+        if let Err(err) = &result {
+            info.elapsed = started.elapsed();
+            for hook in &self.hooks {
+                hook.on_error(&info, err);
+            }
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(error = %err, "request failed");
+        }
+
+        #[cfg(feature = "metrics")]
+        self.metrics(|metrics| {
+            let status = match &result {
+                Ok(response) => Some(response.status.as_u16()),
+                Err(err) => err.status_code().map(|status| status.as_u16()),
+            };
+            let route = metrics::route(&self.base_url, &info.url);
+            metrics.record_request(&info.method, &route, status, info.elapsed);
+        });
+
+        (result, retry_after)
+    }
+
+    /// Records usage statistics, if the client was configured with `Metrics`.
+    #[cfg(feature = "metrics")]
+    fn metrics(&self, record: impl FnOnce(&Metrics)) {
+        if let Some(metrics) = &self.metrics {
+            record(metrics);
         }
     }
 
+    /// Records `err` as the error a public method fails with.
+    /// Internal helpers such as `send` and `fetch` do not record errors,
+    /// so that errors the client handles itself, such as a missing `/info` endpoint, are not counted.
+    fn failed(&self, err: ClientError) -> ClientError {
+        #[cfg(feature = "metrics")]
+        self.metrics(|metrics| metrics.record_error(&err));
+
+        err
+    }
+
     /// Handles the response from the remote service, checking for errors.
-    async fn response(&self, response: reqwest::Response) -> Result<String, ClientError> {
-        if !response.status().is_success() {
-            let s_err: ErrorResponse =
-                serde_json::from_str(&response.text().await.map_err(ClientError::Reqwest)?)
-                    .map_err(ClientError::Serde)?;
+    async fn response(
+        &self,
+        response: reqwest::Response,
+        info: &mut RequestInfo,
+        started: Instant,
+    ) -> Result<RawResponse, ClientError> {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await.map_err(ClientError::Reqwest)?;
+        info.elapsed = started.elapsed();
 
-            return Err(ClientError::ServerError(s_err));
+        let mut response = ResponseInfo {
+            status,
+            headers,
+            body,
+        };
+        for hook in &self.hooks {
+            hook.on_response(info, &mut response);
+        }
+
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::Span::current();
+            span.record("status", response.status.as_u16());
+            span.record("latency_ms", info.elapsed.as_millis() as u64);
+            span.record("bytes", response.body.len());
+            tracing::debug!("received response");
         }
 
-        response.text().await.map_err(ClientError::Reqwest)
+        error_for_status(response.status, &response.headers, &response.body)?;
+
+        Ok(RawResponse {
+            status: response.status,
+            headers: response.headers,
+            body: response.body,
+        })
     }
 
-    fn user_agent() -> String {
+    pub(crate) fn user_agent() -> String {
         format!(
             "{}/{} (reqwest; {})",
             env!("CARGO_PKG_NAME"),
@@ -250,21 +1381,23 @@ impl CodesClient {
         )
     }
 
+    pub(crate) fn default_headers() -> reqwest::header::HeaderMap {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::ACCEPT,
+            reqwest::header::HeaderValue::from_static("application/json"),
+        );
+        headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            reqwest::header::HeaderValue::from_static("application/json"),
+        );
+        headers
+    }
+
     pub fn default_client() -> reqwest::Client {
         reqwest::Client::builder()
             .user_agent(Self::user_agent())
-            .default_headers({
-                let mut headers = reqwest::header::HeaderMap::new();
-                headers.insert(
-                    reqwest::header::ACCEPT,
-                    reqwest::header::HeaderValue::from_static("application/json"),
-                );
-                headers.insert(
-                    reqwest::header::CONTENT_TYPE,
-                    reqwest::header::HeaderValue::from_static("application/json"),
-                );
-                headers
-            })
+            .default_headers(Self::default_headers())
             .build()
             .unwrap_or_else(|_| reqwest::Client::new())
     }
@@ -276,44 +1409,126 @@ impl Default for CodesClient {
             base_url: DEFAULT_BASE_URL.to_string(),
             api_key: None,
             client: Self::default_client(),
+            retry_policy: RetryPolicy::none(),
+            rate_limiter: None,
+            rate_limited_wait: None,
+            cache: None,
+            decode_mode: DecodeMode::Strict,
+            hooks: Vec::new(),
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "write")]
+            authenticator: None,
+            server_info: Arc::default(),
+            #[cfg(feature = "write")]
+            batch_insert: Arc::default(),
+        }
+    }
+}
+
+/// Checks the status of a response from the remote service, turning errors into a `ClientError`.
+/// 304 Not Modified is not an error, as it only occurs in response to our own conditional requests.
+pub(crate) fn error_for_status(
+    status: reqwest::StatusCode,
+    headers: &reqwest::header::HeaderMap,
+    body: &str,
+) -> Result<(), ClientError> {
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(ClientError::RateLimited {
+            retry_after: retry::retry_after(headers),
+        });
+    }
+
+    if !status.is_success() && status != reqwest::StatusCode::NOT_MODIFIED {
+        return Err(match serde_json::from_str::<ErrorResponse>(body) {
+            Ok(s_err) => ClientError::ServerError(s_err),
+            Err(_) => ClientError::UnexpectedResponse {
+                status,
+                body: body.to_string(),
+            },
+        });
+    }
+
+    Ok(())
+}
+
+/// The route of a single code.
+/// Codes may contain symbols such as `#` and `&`, so anything but letters, digits and dashes is percent-encoded.
+#[cfg(feature = "write")]
+pub(crate) fn code_route(code: &ChestCode) -> String {
+    let mut route = String::from("/codes/");
+
+    for byte in code.as_str().bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' {
+            route.push(byte as char);
+        } else {
+            route.push_str(&format!("%{:02X}", byte));
         }
     }
+
+    route
+}
+
+/// Parses the response of inserting a code, which is the ID of the new code.
+/// Newer versions of the remote respond with `{"id": 1}` instead of `1`, both are accepted.
+#[cfg(feature = "write")]
+pub(crate) fn parse_insert_id(body: &str) -> Option<i32> {
+    #[derive(serde::Deserialize)]
+    struct Inserted {
+        id: i32,
+    }
+
+    let body = body.trim();
+    body.parse::<i32>().ok().or_else(|| {
+        serde_json::from_str::<Inserted>(body)
+            .ok()
+            .map(|inserted| inserted.id)
+    })
 }
 
-fn mapping_slim(codes: RetrieveCodesResponse) -> Vec<Code> {
+pub(crate) fn mapping_slim(codes: RetrieveCodesResponse) -> Vec<Code> {
     codes
         .codes
         .into_iter()
-        .map(|code| Code {
-            code: code.code,
-            expired: code.expired,
-            expires_at: None,
-            creator: None,
-            submitter: None,
-            lister: None,
+        .filter_map(|code| {
+            Some(Code {
+                code: code.code.valid()?,
+                expired: code.expired,
+                expires_at: None,
+                creator: None,
+                submitter: None,
+                lister: None,
+            })
         })
         .collect::<Vec<Code>>()
 }
 
-fn mapping_full(codes: RetrieveCodesResponse) -> Vec<Code> {
-    codes
+pub(crate) fn mapping_full(codes: RetrieveCodesResponse) -> Vec<Code> {
+    mapping_with_sources(codes).0
+}
+
+/// Like `mapping_full`, but also returns the sources table the codes refer to.
+pub(crate) fn mapping_with_sources(codes: RetrieveCodesResponse) -> (Vec<Code>, SourceIndex) {
+    let mapped = codes
         .codes
         .into_iter()
-        .map(|code| {
+        .filter_map(|code| {
             let creator = codes.sources.get(&code.sources.creator).cloned();
             let submitter = codes.sources.get(&code.sources.submitter).cloned();
             let lister = codes.sources.get(&code.sources.lister).cloned();
 
-            Code {
-                code: code.code,
+            Some(Code {
+                code: code.code.valid()?,
                 expired: code.expired,
                 expires_at: Some(code.expires_at),
                 creator,
                 submitter,
                 lister,
-            }
+            })
         })
-        .collect::<Vec<Code>>()
+        .collect::<Vec<Code>>();
+
+    (mapped, SourceIndex::from(codes.sources))
 }
 
 #[cfg(test)]
@@ -342,6 +1557,36 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_rate_limited_wait() {
+        let client = CodesClient::default();
+        assert_eq!(
+            client.rate_limited_wait(Some(Duration::from_secs(1)), 0),
+            None
+        );
+
+        let client = CodesClient::builder()
+            .wait_on_rate_limited(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.rate_limited_wait(Some(Duration::from_secs(5)), 0),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            client.rate_limited_wait(None, 0),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            client.rate_limited_wait(Some(Duration::from_secs(60)), 0),
+            None
+        );
+        assert_eq!(
+            client.rate_limited_wait(Some(Duration::from_secs(5)), MAX_RATE_LIMITED_WAITS),
+            None
+        );
+    }
+
     #[test]
     fn test_client_url() {
         let client = CodesClient::default();
@@ -362,6 +1607,14 @@ mod test {
         assert!(m[0].lister.is_none());
     }
 
+    #[test]
+    fn test_mapping_with_sources() {
+        let (codes, sources) = mapping_with_sources(mock_response());
+        assert_eq!(codes.len(), 1);
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources.get(1).unwrap().name, "foo");
+    }
+
     #[test]
     fn test_mapping_full() {
         let m = mapping_full(mock_response());
@@ -370,6 +1623,11 @@ mod test {
         assert!(!m[0].expired);
         assert!(m[0].expires_at.is_some());
         assert_eq!(m[0].expires_at, Some("2024-01-01 00:00:00.0".to_string()));
+        #[cfg(feature = "chrono")]
+        assert_eq!(
+            m[0].expires_at_parsed(),
+            Some(Ok(chrono::DateTime::from_timestamp(1704067200, 0).unwrap()))
+        );
         assert!(m[0].creator.is_some());
         assert!(m[0].submitter.is_some());
         assert!(m[0].lister.is_some());
@@ -384,6 +1642,79 @@ mod test {
         assert!(output.is_ok());
     }
 
+    #[test]
+    fn test_error_for_status() {
+        let headers = reqwest::header::HeaderMap::new();
+
+        assert!(error_for_status(reqwest::StatusCode::OK, &headers, "").is_ok());
+
+        let err = error_for_status(
+            reqwest::StatusCode::UNAUTHORIZED,
+            &headers,
+            r#"{"error":{"code":401,"description":"Invalid API key","debug":null}}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ClientError::ServerError(_)));
+        assert!(err.is_unauthorized());
+        assert_eq!(
+            err.to_string(),
+            "the remote returned an error (401): Invalid API key"
+        );
+
+        let err = error_for_status(
+            reqwest::StatusCode::BAD_GATEWAY,
+            &headers,
+            "<html>Bad Gateway</html>",
+        )
+        .unwrap_err();
+        assert!(matches!(err, ClientError::UnexpectedResponse { .. }));
+        assert_eq!(err.status_code(), Some(reqwest::StatusCode::BAD_GATEWAY));
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn test_error_classification() {
+        let not_found = ClientError::UnexpectedResponse {
+            status: reqwest::StatusCode::NOT_FOUND,
+            body: String::new(),
+        };
+        assert!(not_found.is_not_found());
+        assert!(!not_found.is_retryable());
+
+        let exhausted = ClientError::RetriesExhausted {
+            attempts: 3,
+            last: Box::new(ClientError::UnexpectedResponse {
+                status: reqwest::StatusCode::GATEWAY_TIMEOUT,
+                body: String::new(),
+            }),
+        };
+        assert!(exhausted.is_timeout());
+        assert!(exhausted.is_retryable());
+        assert_eq!(
+            exhausted.status_code(),
+            Some(reqwest::StatusCode::GATEWAY_TIMEOUT)
+        );
+        assert!(std::error::Error::source(&exhausted).is_some());
+
+        let rate_limited = ClientError::RateLimited {
+            retry_after: Some(Duration::from_secs(30)),
+        };
+        assert!(rate_limited.is_retryable());
+        assert_eq!(
+            rate_limited.to_string(),
+            "rate limited by the remote, retry after 30s"
+        );
+    }
+
+    #[test]
+    fn test_serde_error_display() {
+        let err = decode::decode::<ErrorResponse>(r#"{"error":{"code":"401"}}"#).unwrap_err();
+
+        assert_eq!(err.to_string(), "failed to (de)serialize JSON");
+        let source = std::error::Error::source(&err).unwrap().to_string();
+        assert!(source.starts_with("invalid JSON at `error.code` near `"));
+    }
+
     #[test]
     fn test_can_deserialize_remote_error() {
         let output = serde_json::from_str::<ErrorResponse>(
@@ -397,7 +1728,7 @@ mod test {
     #[cfg(feature = "write")]
     fn test_it_can_serialize_insert_request() {
         let insert_request = write::InsertCodeRequest {
-            code: "FOOB-BARS-TEST".to_string(),
+            code: "FOOB-BARS-TEST".parse().unwrap(),
             expires_at: 800,
             creator: write::SourceLookup {
                 name: "Example Creator".to_string(),
@@ -420,6 +1751,101 @@ mod test {
         );
     }
 
+    #[test]
+    #[cfg(feature = "write")]
+    fn test_it_can_serialize_update_request() {
+        let mut update_request = write::UpdateCodeRequest::new("FOOB-BARS-TEST".parse().unwrap());
+        update_request.expired = Some(false);
+        update_request.submitter = Some(write::SourceLookup {
+            name: "Example Submitter".to_string(),
+            url: "https://submitter.example.org".to_string(),
+        });
+
+        let remote_request = puts::RemoteUpdateCodeRequest::from(update_request);
+
+        assert_eq!(
+            serde_json::to_string(&remote_request).unwrap(),
+            r#"{"expired":false,"submitter_name":"Example Submitter","submitter_url":"https://submitter.example.org"}"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn test_it_can_serialize_source_requests() {
+        let create = puts::RemoteSourceRequest::from(write::SourceLookup {
+            name: "Example Creator".to_string(),
+            url: "https://creator.example.org".to_string(),
+        });
+        assert_eq!(
+            serde_json::to_string(&create).unwrap(),
+            r#"{"name":"Example Creator","url":"https://creator.example.org"}"#
+        );
+
+        let mut update_request = write::UpdateSourceRequest::new(7);
+        update_request.url = Some("https://creator.example.org".to_string());
+        let update = puts::RemoteUpdateSourceRequest::from(update_request);
+        assert_eq!(
+            serde_json::to_string(&update).unwrap(),
+            r#"{"url":"https://creator.example.org"}"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn test_parse_insert_id() {
+        assert_eq!(parse_insert_id("12"), Some(12));
+        assert_eq!(parse_insert_id("{\"id\": 12}\n"), Some(12));
+        assert_eq!(parse_insert_id("created"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn test_code_route() {
+        assert_eq!(
+            code_route(&"FOOB-BARS-TEST".parse().unwrap()),
+            "/codes/FOOB-BARS-TEST"
+        );
+        assert_eq!(
+            code_route(&"L0V3-Y0UR-D1C3-!#$&".parse().unwrap()),
+            "/codes/L0V3-Y0UR-D1C3-%21%23%24%26"
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "write")]
+    async fn test_write_requires_api_key() {
+        let client = CodesClient::default();
+        let code = "FOOB-BARS-TEST".parse().unwrap();
+
+        assert!(matches!(
+            client.delete_code(&code).await,
+            Err(ClientError::ApiKeyMissing)
+        ));
+        assert!(matches!(
+            client.expire_code(&code).await,
+            Err(ClientError::ApiKeyMissing)
+        ));
+        assert!(matches!(
+            client.insert_codes(Vec::new()).await,
+            Err(ClientError::ApiKeyMissing)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn test_authenticator() {
+        assert!(CodesClient::default().authenticator().is_err());
+        assert!(CodesClient::new(Some(ApiKey::new("secret".to_string())))
+            .authenticator()
+            .is_ok());
+
+        let client = CodesClient::builder()
+            .authenticator(BearerToken(ApiKey::new("secret".to_string())))
+            .build()
+            .unwrap();
+        assert!(client.authenticator().is_ok());
+    }
+
     fn mock_response() -> RetrieveCodesResponse {
         let mut sources = HashMap::new();
         sources.insert(
@@ -449,7 +1875,7 @@ mod test {
 
         RetrieveCodesResponse {
             codes: vec![RetrieveCodesCodeResponse {
-                code: "FOOB-BARS-TEST".to_string(),
+                code: decode::ListedCode::from("FOOB-BARS-TEST".to_string()),
                 expired: false,
                 expires_at: "2024-01-01 00:00:00.0".to_string(),
                 sources: SourcesMapping {
@@ -459,6 +1885,7 @@ mod test {
                 },
             }],
             sources,
+            next_cursor: None,
         }
     }
 }
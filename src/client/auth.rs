@@ -0,0 +1,259 @@
+use crate::api_key::ApiKey;
+use crate::client::error::ClientError;
+use reqwest::header::{HeaderName, HeaderValue};
+
+/// Adds credentials to the requests a `CodesClient` sends to modify the remote.
+///
+/// The client calls `authenticate` for every attempt, so schemes that sign requests with a timestamp
+/// or nonce produce a fresh signature when a request is retried.
+/// See `CodesClientBuilder::authenticator`; by default the API key is sent with `ApiKeyHeader`.
+pub trait Authenticator: Send + Sync {
+    fn authenticate(&self, request: &mut reqwest::Request) -> Result<(), ClientError>;
+}
+
+/// Sends the API key as-is in the `X-Api-Key` header.
+#[derive(Clone, Debug)]
+pub struct ApiKeyHeader(pub ApiKey);
+
+/// Sends a token in the `Authorization: Bearer` header.
+#[derive(Clone, Debug)]
+pub struct BearerToken(pub ApiKey);
+
+impl Authenticator for ApiKeyHeader {
+    fn authenticate(&self, request: &mut reqwest::Request) -> Result<(), ClientError> {
+        let value = sensitive(self.0.get())?;
+        request
+            .headers_mut()
+            .insert(HeaderName::from_static("x-api-key"), value);
+
+        Ok(())
+    }
+}
+
+impl Authenticator for BearerToken {
+    fn authenticate(&self, request: &mut reqwest::Request) -> Result<(), ClientError> {
+        let value = sensitive(&format!("Bearer {}", self.0.get()))?;
+        request
+            .headers_mut()
+            .insert(reqwest::header::AUTHORIZATION, value);
+
+        Ok(())
+    }
+}
+
+/// A header value that is redacted when the request is logged.
+fn sensitive(value: &str) -> Result<HeaderValue, ClientError> {
+    let mut value = HeaderValue::from_str(value).map_err(|_| ClientError::Authentication {
+        reason: "the credentials are not a valid header value",
+    })?;
+    value.set_sensitive(true);
+
+    Ok(value)
+}
+
+#[cfg(feature = "signing")]
+pub use signing::HmacSigner;
+
+#[cfg(feature = "signing")]
+mod signing {
+    use super::*;
+    use hmac::{Hmac, Mac};
+    use sha2::{Digest, Sha256};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// Signs requests with HMAC-SHA256, so the API key itself is never sent.
+    ///
+    /// The signature covers the method, the path and query, a timestamp, a random nonce and
+    /// the SHA-256 hash of the body, each followed by a newline:
+    ///
+    /// ```text
+    /// PUT
+    /// /v1/codes
+    /// 1700000000
+    /// 3f2a0c...
+    /// 9d5e3f...
+    /// ```
+    ///
+    /// and is sent as lowercase hex in the `X-Signature` header, along with the `X-Signature-Timestamp`,
+    /// `X-Signature-Nonce` and `X-Content-Sha256` headers, and `X-Api-Key-Id` if a key ID is set.
+    #[derive(Clone, Debug)]
+    pub struct HmacSigner {
+        key: ApiKey,
+        key_id: Option<String>,
+    }
+
+    impl HmacSigner {
+        pub fn new(key: ApiKey) -> Self {
+            Self { key, key_id: None }
+        }
+
+        /// Tells the remote which key signed the request, for remotes that accept more than one key.
+        pub fn key_id(mut self, key_id: impl Into<String>) -> Self {
+            self.key_id = Some(key_id.into());
+            self
+        }
+
+        /// The lowercase hex HMAC-SHA256 signature of a request.
+        pub fn signature(
+            &self,
+            method: &str,
+            path: &str,
+            timestamp: u64,
+            nonce: &str,
+            body_sha256: &str,
+        ) -> String {
+            let mut mac = Hmac::<Sha256>::new_from_slice(self.key.get().as_bytes())
+                .expect("HMAC accepts keys of any length");
+            mac.update(
+                format!(
+                    "{}\n{}\n{}\n{}\n{}\n",
+                    method, path, timestamp, nonce, body_sha256
+                )
+                .as_bytes(),
+            );
+
+            hex(&mac.finalize().into_bytes())
+        }
+    }
+
+    impl Authenticator for HmacSigner {
+        fn authenticate(&self, request: &mut reqwest::Request) -> Result<(), ClientError> {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let nonce = format!("{:016x}{:016x}", fastrand::u64(..), fastrand::u64(..));
+
+            let body = request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .unwrap_or_default();
+            let body_sha256 = hex(&Sha256::digest(body));
+
+            let path = match request.url().query() {
+                Some(query) => format!("{}?{}", request.url().path(), query),
+                None => request.url().path().to_string(),
+            };
+            let signature = self.signature(
+                request.method().as_str(),
+                &path,
+                timestamp,
+                &nonce,
+                &body_sha256,
+            );
+
+            let mut headers = vec![
+                ("x-signature", signature),
+                ("x-signature-timestamp", timestamp.to_string()),
+                ("x-signature-nonce", nonce),
+                ("x-content-sha256", body_sha256),
+            ];
+            if let Some(key_id) = &self.key_id {
+                headers.push(("x-api-key-id", key_id.clone()));
+            }
+
+            for (name, value) in headers {
+                let value =
+                    HeaderValue::from_str(&value).map_err(|_| ClientError::Authentication {
+                        reason: "the key ID is not a valid header value",
+                    })?;
+                request
+                    .headers_mut()
+                    .insert(HeaderName::from_static(name), value);
+            }
+
+            Ok(())
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn test_signature() {
+            let signer = HmacSigner::new(ApiKey::new("secret".to_string()));
+
+            // echo -en 'PUT\n/v1/codes\n1700000000\nabc\ndef\n' | openssl dgst -sha256 -hmac secret
+            assert_eq!(
+                signer.signature("PUT", "/v1/codes", 1700000000, "abc", "def"),
+                "fc08795a92603615ca8aca42033d0d0f2077f3f04d9a6751b728bc6bd4e10777"
+            );
+        }
+
+        #[test]
+        fn test_authenticate() {
+            let signer = HmacSigner::new(ApiKey::new("secret".to_string())).key_id("bot");
+            let mut request = reqwest::Client::new()
+                .put("http://localhost/v1/codes?foo=bar")
+                .body("{}")
+                .build()
+                .unwrap();
+
+            signer.authenticate(&mut request).unwrap();
+
+            let header = |name: &str| request.headers()[name].to_str().unwrap().to_string();
+            assert_eq!(
+                header("x-content-sha256"),
+                "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+            );
+            assert_eq!(header("x-api-key-id"), "bot");
+            assert!(request.headers().get("x-api-key").is_none());
+
+            let expected = signer.signature(
+                "PUT",
+                "/v1/codes?foo=bar",
+                header("x-signature-timestamp").parse().unwrap(),
+                &header("x-signature-nonce"),
+                &header("x-content-sha256"),
+            );
+            assert_eq!(header("x-signature"), expected);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request() -> reqwest::Request {
+        reqwest::Client::new()
+            .delete("http://localhost/v1/codes/foo")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_api_key_header() {
+        let mut request = request();
+        ApiKeyHeader(ApiKey::new("secret".to_string()))
+            .authenticate(&mut request)
+            .unwrap();
+
+        assert_eq!(request.headers()["x-api-key"], "secret");
+        assert!(request.headers()["x-api-key"].is_sensitive());
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let mut request = request();
+        BearerToken(ApiKey::new("token".to_string()))
+            .authenticate(&mut request)
+            .unwrap();
+
+        assert_eq!(request.headers()["authorization"], "Bearer token");
+        assert!(request.headers()["authorization"].is_sensitive());
+    }
+
+    #[test]
+    fn test_invalid_credentials() {
+        assert!(matches!(
+            ApiKeyHeader(ApiKey::new("foo\nbar".to_string())).authenticate(&mut request()),
+            Err(ClientError::Authentication { .. })
+        ));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::client::decode;
+use crate::client::error::{ClientError, ErrorResponse, InnerErrorResponse};
+use crate::client::puts::RemoteInsertCodeRequest;
+use crate::client::server_info;
+use crate::ChestCode;
+
+/// How many codes are inserted at the same time when the remote has no batch endpoint.
+pub(crate) const INSERT_CONCURRENCY: usize = 4;
+
+/// The outcome of `CodesClient::insert_codes`, one result per code in the order they were given.
+#[derive(Debug)]
+pub struct InsertReport {
+    pub results: Vec<(ChestCode, Result<Option<i32>, ClientError>)>,
+}
+
+impl InsertReport {
+    /// Codes that were inserted, with their ID if the remote returned one.
+    pub fn inserted(&self) -> impl Iterator<Item = (&ChestCode, Option<i32>)> {
+        self.results
+            .iter()
+            .filter_map(|(code, result)| match result {
+                Ok(id) => Some((code, *id)),
+                Err(_) => None,
+            })
+    }
+
+    /// Codes that were not inserted because the remote already knows them.
+    pub fn conflicts(&self) -> impl Iterator<Item = &ChestCode> {
+        self.results
+            .iter()
+            .filter_map(|(code, result)| match result {
+                Err(err) if err.is_conflict() => Some(code),
+                _ => None,
+            })
+    }
+
+    /// Codes that were not inserted for any other reason than already existing.
+    pub fn failed(&self) -> impl Iterator<Item = (&ChestCode, &ClientError)> {
+        self.results
+            .iter()
+            .filter_map(|(code, result)| match result {
+                Err(err) if !err.is_conflict() => Some((code, err)),
+                _ => None,
+            })
+    }
+}
+
+#[derive(serde::Serialize)]
+pub(crate) struct RemoteBatchInsertRequest {
+    pub(crate) codes: Vec<RemoteInsertCodeRequest>,
+}
+
+#[derive(serde::Deserialize)]
+struct RemoteBatchInsertResponse {
+    results: Vec<RemoteBatchInsertResult>,
+}
+
+/// Either the ID of the inserted code, or why it was not inserted.
+#[derive(serde::Deserialize)]
+struct RemoteBatchInsertResult {
+    id: Option<i32>,
+    error: Option<InnerErrorResponse>,
+}
+
+/// Whether the remote does not have a batch endpoint.
+pub(crate) fn is_unsupported(err: &ClientError) -> bool {
+    server_info::is_missing_endpoint(err)
+}
+
+/// Pairs the results of the batch endpoint with the codes that were sent, in order.
+pub(crate) fn report(codes: Vec<ChestCode>, body: &str) -> Result<InsertReport, ClientError> {
+    let response: RemoteBatchInsertResponse = decode::decode(body)?;
+
+    if response.results.len() != codes.len() {
+        return Err(ClientError::UnexpectedResponse {
+            status: reqwest::StatusCode::OK,
+            body: body.to_string(),
+        });
+    }
+
+    let results = codes
+        .into_iter()
+        .zip(response.results)
+        .map(|(code, result)| match result.error {
+            Some(error) => (code, Err(ClientError::ServerError(ErrorResponse { error }))),
+            None => (code, Ok(result.id)),
+        })
+        .collect();
+
+    Ok(InsertReport { results })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn codes() -> Vec<ChestCode> {
+        ["FOOB-BARS-TES1", "FOOB-BARS-TES2", "FOOB-BARS-TES3"]
+            .iter()
+            .map(|code| code.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_report() {
+        let body = r#"{"results": [
+            {"id": 1},
+            {"error": {"code": 409, "description": "Code already exists", "debug": null}},
+            {"error": {"code": 422, "description": "Invalid expiry", "debug": null}}
+        ]}"#;
+
+        let report = report(codes(), body).unwrap();
+
+        assert_eq!(
+            report.inserted().collect::<Vec<_>>(),
+            vec![(&codes()[0], Some(1))]
+        );
+        assert_eq!(report.conflicts().collect::<Vec<_>>(), vec![&codes()[1]]);
+
+        let failed = report.failed().collect::<Vec<_>>();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, &codes()[2]);
+        assert_eq!(
+            failed[0].1.status_code(),
+            Some(reqwest::StatusCode::UNPROCESSABLE_ENTITY)
+        );
+    }
+
+    #[test]
+    fn test_report_rejects_missing_results() {
+        assert!(matches!(
+            report(codes(), r#"{"results": [{"id": 1}]}"#),
+            Err(ClientError::UnexpectedResponse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_is_unsupported() {
+        let unexpected = |status| ClientError::UnexpectedResponse {
+            status,
+            body: String::new(),
+        };
+
+        assert!(is_unsupported(&unexpected(reqwest::StatusCode::NOT_FOUND)));
+        assert!(is_unsupported(&unexpected(
+            reqwest::StatusCode::METHOD_NOT_ALLOWED
+        )));
+        assert!(!is_unsupported(&unexpected(
+            reqwest::StatusCode::BAD_GATEWAY
+        )));
+    }
+}
@@ -0,0 +1,399 @@
+use crate::api_key::ApiKey;
+use crate::client::error::ClientError;
+#[cfg(feature = "write")]
+use crate::client::Authenticator;
+#[cfg(feature = "metrics")]
+use crate::client::Metrics;
+use crate::client::{
+    CodesClient, DecodeMode, Hook, RateLimiter, ResponseCache, RetryPolicy, DEFAULT_BASE_URL,
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The TLS implementation used by the underlying HTTP client.
+///
+/// Which backends are available depends on the enabled cargo features,
+/// so matching on it needs a wildcard arm.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TlsBackend {
+    /// Let reqwest pick its default backend.
+    #[default]
+    Default,
+    /// Force the `native-tls` backend. Requires the `native-tls` feature.
+    #[cfg(feature = "native-tls")]
+    NativeTls,
+    /// Force the `rustls` backend. Requires the `rustls-tls` feature.
+    #[cfg(feature = "rustls-tls")]
+    Rustls,
+}
+
+/// Builder for a `CodesClient`.
+///
+/// All options are optional, unset options fall back to the same defaults `CodesClient::default` uses.
+/// The base URL is validated when calling `build`.
+///
+/// ```no_run
+/// use licc::client::CodesClientBuilder;
+/// use std::time::Duration;
+///
+/// let client = CodesClientBuilder::new()
+///     .base_url("http://localhost:8000/v1")
+///     .timeout(Duration::from_secs(10))
+///     .user_agent_suffix("my-bot/1.0")
+///     .build()
+///     .expect("valid configuration");
+/// ```
+#[derive(Default)]
+pub struct CodesClientBuilder {
+    base_url: Option<String>,
+    api_key: Option<ApiKey>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    user_agent_suffix: Option<String>,
+    default_headers: HeaderMap,
+    proxy: Option<reqwest::Proxy>,
+    tls_backend: TlsBackend,
+    retry_policy: RetryPolicy,
+    rate_limit: Option<(f64, u32)>,
+    rate_limiter: Option<RateLimiter>,
+    rate_limited_wait: Option<Duration>,
+    cache: Option<Arc<dyn ResponseCache>>,
+    decode_mode: DecodeMode,
+    hooks: Vec<Arc<dyn Hook>>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Metrics>,
+    #[cfg(feature = "write")]
+    authenticator: Option<Arc<dyn Authenticator>>,
+    client: Option<reqwest::Client>,
+}
+
+impl CodesClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The base URL of the remote service, including the version segment, e.g. `https://example.org/v1`.
+    /// A trailing slash is accepted and removed.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// The API Key to use for write requests.
+    pub fn api_key(mut self, api_key: ApiKey) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// How write requests are authenticated, e.g. `HmacSigner` to sign requests instead of sending the API key.
+    /// Takes precedence over the API key.
+    #[cfg(feature = "write")]
+    pub fn authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+
+    /// Total timeout of a single request, from connecting until the response body has been read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout for establishing a connection to the remote.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Appended to the default user agent, useful to let the remote know who is making requests.
+    pub fn user_agent_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.user_agent_suffix = Some(suffix.into());
+        self
+    }
+
+    /// Add a header that is sent with every request.
+    pub fn default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.default_headers.insert(name, value);
+        self
+    }
+
+    /// Add headers that are sent with every request, overriding previously set headers of the same name.
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.default_headers.extend(headers);
+        self
+    }
+
+    /// Route all requests through the given proxy.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Select the TLS backend of the underlying HTTP client.
+    pub fn tls_backend(mut self, tls_backend: TlsBackend) -> Self {
+        self.tls_backend = tls_backend;
+        self
+    }
+
+    /// How transient failures are retried, by default requests are not retried.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Limit the client to on average `requests_per_second` requests, allowing bursts of up to `burst` requests.
+    /// The limit is shared between clones of the built client.
+    /// `build` fails with `ClientError::InvalidRateLimit` if the rate is not a positive, finite number.
+    pub fn rate_limit(mut self, requests_per_second: f64, burst: u32) -> Self {
+        self.rate_limit = Some((requests_per_second, burst));
+        self.rate_limiter = None;
+        self
+    }
+
+    /// Use an existing `RateLimiter`, e.g. to share a single limit between differently configured clients.
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self.rate_limit = None;
+        self
+    }
+
+    /// When the remote responds with 429 Too Many Requests and asks to wait at most `max_wait`,
+    /// wait and send the request again instead of returning `ClientError::RateLimited`.
+    pub fn wait_on_rate_limited(mut self, max_wait: Duration) -> Self {
+        self.rate_limited_wait = Some(max_wait);
+        self
+    }
+
+    /// Cache responses to GET requests, such as `get_codes`, and revalidate them with conditional requests.
+    /// When the remote reports the data did not change, the cached response is used instead of downloading it again.
+    pub fn cache(mut self, cache: Arc<dyn ResponseCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// How strictly lists of codes are decoded, by default any malformed entry fails the request.
+    pub fn decode_mode(mut self, decode_mode: DecodeMode) -> Self {
+        self.decode_mode = decode_mode;
+        self
+    }
+
+    /// Add a `Hook` that observes the requests and responses of the client.
+    /// Hooks are called in the order they were added.
+    pub fn hook(mut self, hook: impl Hook + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Record usage statistics, such as request counts and latencies, into `metrics`.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Use a preconfigured `reqwest::Client`.
+    /// When set, the timeout, user agent, header, proxy and TLS options of this builder are ignored.
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Validate the configuration and construct the `CodesClient`.
+    pub fn build(self) -> Result<CodesClient, ClientError> {
+        let failed = |err: ClientError| {
+            #[cfg(feature = "metrics")]
+            if let Some(metrics) = &self.metrics {
+                metrics.record_error(&err);
+            }
+
+            err
+        };
+
+        let base_url = match &self.base_url {
+            Some(url) => validate_base_url(url).map_err(failed)?,
+            None => DEFAULT_BASE_URL.to_string(),
+        };
+
+        let rate_limiter = match self.rate_limit {
+            Some((requests_per_second, burst)) => {
+                Some(RateLimiter::try_new(requests_per_second, burst).map_err(failed)?)
+            }
+            None => self.rate_limiter,
+        };
+
+        let client = match self.client {
+            Some(client) => client,
+            None => {
+                let user_agent = match &self.user_agent_suffix {
+                    Some(suffix) => format!("{} {}", CodesClient::user_agent(), suffix),
+                    None => CodesClient::user_agent(),
+                };
+
+                let mut headers = CodesClient::default_headers();
+                headers.extend(self.default_headers);
+
+                let mut builder = reqwest::Client::builder()
+                    .user_agent(user_agent)
+                    .default_headers(headers);
+
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(proxy);
+                }
+
+                builder = match self.tls_backend {
+                    TlsBackend::Default => builder,
+                    #[cfg(feature = "native-tls")]
+                    TlsBackend::NativeTls => builder.use_native_tls(),
+                    #[cfg(feature = "rustls-tls")]
+                    TlsBackend::Rustls => builder.use_rustls_tls(),
+                };
+
+                builder
+                    .build()
+                    .map_err(|err| failed(ClientError::Reqwest(err)))?
+            }
+        };
+
+        Ok(CodesClient {
+            base_url,
+            api_key: self.api_key,
+            client,
+            retry_policy: self.retry_policy,
+            rate_limiter,
+            rate_limited_wait: self.rate_limited_wait,
+            cache: self.cache,
+            decode_mode: self.decode_mode,
+            hooks: self.hooks,
+            #[cfg(feature = "metrics")]
+            metrics: self.metrics,
+            #[cfg(feature = "write")]
+            authenticator: self.authenticator,
+            server_info: Default::default(),
+            #[cfg(feature = "write")]
+            batch_insert: Default::default(),
+        })
+    }
+}
+
+/// Checks that `url` is an absolute http(s) URL ending in a version segment such as `/v1`,
+/// and returns it without trailing slashes.
+pub(crate) fn validate_base_url(url: &str) -> Result<String, ClientError> {
+    let invalid = |reason: &'static str| ClientError::InvalidBaseUrl {
+        url: url.to_string(),
+        reason,
+    };
+
+    let parsed = reqwest::Url::parse(url).map_err(|_| invalid("not an absolute URL"))?;
+
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return Err(invalid("scheme must be http or https"));
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(invalid("must not contain a query or fragment"));
+    }
+
+    let version = parsed
+        .path()
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    let is_version = version.len() > 1
+        && version.starts_with('v')
+        && version[1..].chars().all(|c| c.is_ascii_digit());
+
+    if !is_version {
+        return Err(invalid("path must end in an API version, e.g. /v1"));
+    }
+
+    Ok(url.trim_end_matches('/').to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_default() {
+        let client = CodesClientBuilder::new().build().unwrap();
+        assert_eq!(client.base_url, DEFAULT_BASE_URL);
+        assert!(client.api_key.is_none());
+    }
+
+    #[test]
+    fn test_build_with_options() {
+        let client = CodesClientBuilder::new()
+            .base_url("http://localhost:8000/v1/")
+            .api_key(ApiKey::new("foo".to_string()))
+            .timeout(Duration::from_secs(5))
+            .connect_timeout(Duration::from_secs(1))
+            .user_agent_suffix("test/1.0")
+            .retry_policy(RetryPolicy::exponential(3))
+            .rate_limit(5.0, 10)
+            .cache(Arc::new(crate::client::MemoryCache::new(4)))
+            .default_header(
+                HeaderName::from_static("x-correlation-id"),
+                HeaderValue::from_static("abc"),
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(client.base_url, "http://localhost:8000/v1");
+        assert!(client.api_key.is_some());
+        assert_eq!(client.retry_policy.max_attempts(), 3);
+        assert!(client.rate_limiter.is_some());
+        assert!(client.cache.is_some());
+    }
+
+    #[test]
+    fn test_build_rejects_invalid_rate_limit() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                CodesClientBuilder::new().rate_limit(rate, 1).build(),
+                Err(ClientError::InvalidRateLimit { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_validate_base_url() {
+        assert_eq!(
+            validate_base_url("https://example.org/v1").unwrap(),
+            "https://example.org/v1"
+        );
+        assert_eq!(
+            validate_base_url("https://example.org/api/v12//").unwrap(),
+            "https://example.org/api/v12"
+        );
+    }
+
+    #[test]
+    fn test_validate_base_url_rejects_invalid() {
+        for url in [
+            "example.org/v1",
+            "ftp://example.org/v1",
+            "https://example.org",
+            "https://v1",
+            "https://example.org/v",
+            "https://example.org/version1",
+            "https://example.org/v1?foo=bar",
+        ] {
+            assert!(
+                matches!(
+                    validate_base_url(url),
+                    Err(ClientError::InvalidBaseUrl { .. })
+                ),
+                "{} should be rejected",
+                url
+            );
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Numbers the temporary files of `FileCache`, so that concurrent writes never share one.
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A response body as stored in a `ResponseCache`,
+/// together with the validators used to ask the remote whether it changed.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CachedResponse {
+    /// The `ETag` header of the response, sent back as `If-None-Match`
+    pub etag: Option<String>,
+    /// The `Last-Modified` header of the response, sent back as `If-Modified-Since`
+    pub last_modified: Option<String>,
+    /// The raw response body
+    pub body: String,
+}
+
+impl CachedResponse {
+    /// Responses without validators can never be revalidated, so there is no point in storing them.
+    pub(crate) fn is_revalidatable(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+/// Storage for responses of conditional GET requests, keyed by the request URL.
+///
+/// Implementations must be usable from multiple tasks at once.
+/// Failing to read or write is not an error, the request is simply made without the cache.
+pub trait ResponseCache: Send + Sync {
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    fn put(&self, key: &str, response: CachedResponse);
+}
+
+/// The result of a request that may have been served from the `ResponseCache`.
+#[derive(Debug)]
+pub struct Fetched<T> {
+    pub value: T,
+    /// True if the remote responded with 304 Not Modified and `value` was built from the cached response.
+    pub from_cache: bool,
+    /// Entries of the response that were skipped because they failed to decode, see `DecodeMode::Lenient`.
+    pub warnings: Vec<crate::client::DecodeWarning>,
+}
+
+/// An in-memory `ResponseCache` holding at most `capacity` responses,
+/// evicting the least recently used response when full.
+#[derive(Debug)]
+pub struct MemoryCache {
+    capacity: usize,
+    inner: Mutex<MemoryCacheInner>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryCacheInner {
+    entries: HashMap<String, CachedResponse>,
+    /// Keys ordered from least to most recently used
+    order: VecDeque<String>,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            inner: Mutex::new(MemoryCacheInner::default()),
+        }
+    }
+
+    fn inner(&self) -> std::sync::MutexGuard<'_, MemoryCacheInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MemoryCacheInner {
+    fn touch(&mut self, key: &str) {
+        if let Some(position) = self.order.iter().position(|k| k == key) {
+            let key = self.order.remove(position).unwrap();
+            self.order.push_back(key);
+        }
+    }
+}
+
+impl ResponseCache for MemoryCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut inner = self.inner();
+        let response = inner.entries.get(key).cloned()?;
+        inner.touch(key);
+
+        Some(response)
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        let mut inner = self.inner();
+
+        if inner.entries.insert(key.to_string(), response).is_some() {
+            inner.touch(key);
+            return;
+        }
+
+        inner.order.push_back(key.to_string());
+        while inner.order.len() > self.capacity {
+            if let Some(evicted) = inner.order.pop_front() {
+                inner.entries.remove(&evicted);
+            }
+        }
+    }
+}
+
+/// A `ResponseCache` storing every response as a JSON file in a directory,
+/// so that the cache survives restarts.
+#[derive(Debug)]
+pub struct FileCache {
+    directory: PathBuf,
+}
+
+/// A response as stored by `FileCache`, with its key to tell apart keys whose file names collide.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FileCacheEntry {
+    key: String,
+    #[serde(flatten)]
+    response: CachedResponse,
+}
+
+impl FileCache {
+    /// Store responses in `directory`, which is created on first write if it does not exist.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        // Keys are URLs of any length, a fixed-length hash keeps the file name within file system limits
+        // and stable across runs and versions.
+        self.directory.join(format!("{:032x}.json", fnv1a(key)))
+    }
+}
+
+/// The 128-bit FNV-1a hash of `key`.
+fn fnv1a(key: &str) -> u128 {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    key.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u128::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+impl ResponseCache for FileCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let contents = std::fs::read_to_string(self.path(key)).ok()?;
+        let entry: FileCacheEntry = serde_json::from_str(&contents).ok()?;
+
+        (entry.key == key).then_some(entry.response)
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        let entry = FileCacheEntry {
+            key: key.to_string(),
+            response,
+        };
+        let Ok(contents) = serde_json::to_string(&entry) else {
+            return;
+        };
+
+        if std::fs::create_dir_all(&self.directory).is_err() {
+            return;
+        }
+
+        // Write to a temporary file first, so a concurrent reader never sees a partial file.
+        // The name is unique per write, as other threads and processes may write the same key at the same time.
+        let path = self.path(key);
+        let tmp = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        if std::fs::write(&tmp, contents).is_err() || std::fs::rename(&tmp, &path).is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn response(body: &str) -> CachedResponse {
+        CachedResponse {
+            etag: Some(format!("\"{}\"", body)),
+            last_modified: None,
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_memory_cache() {
+        let cache = MemoryCache::new(10);
+        assert!(cache.get("a").is_none());
+
+        cache.put("a", response("foo"));
+        assert_eq!(cache.get("a"), Some(response("foo")));
+
+        cache.put("a", response("bar"));
+        assert_eq!(cache.get("a"), Some(response("bar")));
+    }
+
+    #[test]
+    fn test_memory_cache_evicts_least_recently_used() {
+        let cache = MemoryCache::new(2);
+
+        cache.put("a", response("a"));
+        cache.put("b", response("b"));
+        cache.get("a");
+        cache.put("c", response("c"));
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+    }
+
+    #[test]
+    fn test_file_cache() {
+        let directory =
+            std::env::temp_dir().join(format!("licc-test-cache-{}", std::process::id()));
+        let cache = FileCache::new(&directory);
+
+        assert!(cache.get("https://example.org/v1/codes").is_none());
+
+        cache.put("https://example.org/v1/codes", response("foo"));
+        assert_eq!(
+            cache.get("https://example.org/v1/codes"),
+            Some(response("foo"))
+        );
+        assert!(cache.get("https://example.org/v1/other").is_none());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_file_cache_long_key() {
+        let directory =
+            std::env::temp_dir().join(format!("licc-test-cache-long-{}", std::process::id()));
+        let cache = FileCache::new(&directory);
+        let key = format!(
+            "https://example.org/v1/codes?creator_name={}&limit=50",
+            "a".repeat(300)
+        );
+
+        cache.put(&key, response("foo"));
+        assert_eq!(cache.get(&key), Some(response("foo")));
+        assert!(cache.path(&key).file_name().unwrap().len() < 64);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_file_cache_concurrent_puts() {
+        let directory =
+            std::env::temp_dir().join(format!("licc-test-cache-concurrent-{}", std::process::id()));
+        let cache = FileCache::new(&directory);
+        let bodies: Vec<String> = (0..8).map(|i| i.to_string().repeat(10_000)).collect();
+
+        std::thread::scope(|scope| {
+            for body in &bodies {
+                let cache = &cache;
+                scope.spawn(move || {
+                    for _ in 0..10 {
+                        cache.put("https://example.org/v1/codes", response(body));
+                    }
+                });
+            }
+        });
+
+        let cached = cache.get("https://example.org/v1/codes").unwrap();
+        assert!(bodies.iter().any(|body| cached == response(body)));
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(""), 0x6c62272e07bb014262b821756295c58d);
+        assert_eq!(fnv1a("a"), 0xd228cb696f1a8caf78912b704e4a8964);
+    }
+
+    #[test]
+    fn test_is_revalidatable() {
+        assert!(response("foo").is_revalidatable());
+        assert!(!CachedResponse {
+            etag: None,
+            last_modified: None,
+            body: String::new(),
+        }
+        .is_revalidatable());
+    }
+}
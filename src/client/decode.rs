@@ -0,0 +1,246 @@
+use crate::client::error::{ClientError, SerdeError};
+use crate::client::{RetrieveCodesCodeResponse, RetrieveCodesResponse};
+use crate::{ChestCode, ParseChestCodeError, Source};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+
+/// How strictly the list of codes returned by the remote is decoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// Any malformed entry fails the whole request with `ClientError::Serde`.
+    /// Codes that are not a valid `ChestCode` are still skipped and reported as a `DecodeWarning`,
+    /// the remote does not validate the codes it lists.
+    #[default]
+    Strict,
+    /// Malformed codes and sources are skipped and reported as a `DecodeWarning`,
+    /// only a response that is not a list of codes at all fails the request.
+    Lenient,
+}
+
+/// An entry that was skipped while decoding in `DecodeMode::Lenient`.
+#[derive(Debug)]
+pub struct DecodeWarning {
+    /// The `code` field of the skipped entry, if it had one.
+    /// Set for skipped codes only, sources are identified by the path of the error.
+    pub code: Option<String>,
+    /// Why the entry was skipped, including its JSON path
+    pub error: SerdeError,
+}
+
+/// Deserialize `body`, reporting the JSON path and surroundings of the offending value on failure.
+pub(crate) fn decode<T: DeserializeOwned>(body: &str) -> Result<T, ClientError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(body);
+
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let path = err.path().to_string();
+        ClientError::Serde(SerdeError::new(err.into_inner(), Some(path), Some(body)))
+    })
+}
+
+/// A code as listed by the remote, which is not necessarily a valid `ChestCode`.
+#[derive(serde::Deserialize)]
+#[serde(from = "String")]
+pub(crate) enum ListedCode {
+    Valid(ChestCode),
+    Invalid(String, ParseChestCodeError),
+}
+
+impl ListedCode {
+    pub(crate) fn valid(self) -> Option<ChestCode> {
+        match self {
+            Self::Valid(code) => Some(code),
+            Self::Invalid(..) => None,
+        }
+    }
+}
+
+impl From<String> for ListedCode {
+    fn from(code: String) -> Self {
+        match code.parse() {
+            Ok(code) => Self::Valid(code),
+            Err(err) => Self::Invalid(code, err),
+        }
+    }
+}
+
+/// The list of codes and sources, where the entries themselves are not decoded yet.
+#[derive(serde::Deserialize)]
+struct LenientRetrieveCodesResponse {
+    codes: Vec<serde_json::Value>,
+    sources: HashMap<i32, serde_json::Value>,
+    #[serde(default)]
+    next_cursor: Option<String>,
+}
+
+pub(crate) fn decode_codes(
+    body: &str,
+    mode: DecodeMode,
+) -> Result<(RetrieveCodesResponse, Vec<DecodeWarning>), ClientError> {
+    if mode == DecodeMode::Strict {
+        let mut response: RetrieveCodesResponse = decode(body)?;
+        let mut warnings = Vec::new();
+
+        let codes = std::mem::take(&mut response.codes);
+        for (index, code) in codes.into_iter().enumerate() {
+            match invalid_code(index, &code.code) {
+                Some(warning) => warnings.push(warning),
+                None => response.codes.push(code),
+            }
+        }
+
+        return Ok((response, warnings));
+    }
+
+    let lenient: LenientRetrieveCodesResponse = decode(body)?;
+    let mut warnings = Vec::new();
+    let mut codes = Vec::with_capacity(lenient.codes.len());
+    let mut sources = HashMap::with_capacity(lenient.sources.len());
+
+    for (index, value) in lenient.codes.into_iter().enumerate() {
+        match decode_value::<RetrieveCodesCodeResponse>(&value, &format!("codes[{}]", index)) {
+            Ok(code) => match invalid_code(index, &code.code) {
+                Some(warning) => warnings.push(warning),
+                None => codes.push(code),
+            },
+            Err(error) => warnings.push(DecodeWarning {
+                code: value
+                    .get("code")
+                    .and_then(|code| code.as_str())
+                    .map(str::to_string),
+                error,
+            }),
+        }
+    }
+
+    for (id, value) in lenient.sources {
+        match decode_value::<Source>(&value, &format!("sources.{}", id)) {
+            Ok(source) => {
+                sources.insert(id, source);
+            }
+            Err(error) => warnings.push(DecodeWarning { code: None, error }),
+        }
+    }
+
+    let response = RetrieveCodesResponse {
+        codes,
+        sources,
+        next_cursor: lenient.next_cursor,
+    };
+
+    Ok((response, warnings))
+}
+
+/// A warning for the code at `index` of the list, if it is not a valid `ChestCode`.
+fn invalid_code(index: usize, code: &ListedCode) -> Option<DecodeWarning> {
+    let ListedCode::Invalid(code, err) = code else {
+        return None;
+    };
+
+    let error =
+        <serde_json::Error as serde::de::Error>::custom(format!("invalid chest code: {}", err));
+
+    Some(DecodeWarning {
+        code: Some(code.clone()),
+        error: SerdeError::new(error, Some(format!("codes[{}].code", index)), Some(code)),
+    })
+}
+
+/// Deserialize a single entry, prefixing the path of any error with `prefix`.
+fn decode_value<T: DeserializeOwned>(
+    value: &serde_json::Value,
+    prefix: &str,
+) -> Result<T, SerdeError> {
+    serde_path_to_error::deserialize(value).map_err(|err| {
+        let path = match err.path().to_string().as_str() {
+            "." => prefix.to_string(),
+            path => format!("{}.{}", prefix, path),
+        };
+        SerdeError::new(err.into_inner(), Some(path), Some(&value.to_string()))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BODY: &str = r#"{
+        "codes": [
+            {"code": "FOOB-BARS-TEST", "expired": false, "expires_at": "2024-01-01 00:00:00.0", "sources": {"creator": 1, "submitter": 1, "lister": 1}},
+            {"code": "TOO-SHORT", "expired": false, "expires_at": "2024-01-01 00:00:00.0", "sources": {"creator": 1, "submitter": 1, "lister": 1}},
+            {"code": "FOOB-BARS-TES2", "expired": "no", "expires_at": "2024-01-01 00:00:00.0", "sources": {"creator": 1, "submitter": 1, "lister": 1}}
+        ],
+        "sources": {
+            "1": {"id": 1, "name": "foo", "url": "https://foo.example"},
+            "2": {"id": 2, "name": null, "url": "https://bar.example"}
+        }
+    }"#;
+
+    #[test]
+    fn test_strict_reports_path_and_snippet() {
+        let err = decode_codes(BODY, DecodeMode::Strict).err().unwrap();
+
+        let ClientError::Serde(err) = err else {
+            unreachable!("Expected Serde, got {:?}", err);
+        };
+        assert_eq!(err.path.as_deref(), Some("codes[2].expired"));
+        assert!(err.snippet.unwrap().contains("\"no\""));
+    }
+
+    #[test]
+    fn test_strict_skips_invalid_codes() {
+        let body = r#"{
+            "codes": [
+                {"code": "TOO-SHORT", "expired": false, "expires_at": "", "sources": {"creator": 1, "submitter": 1, "lister": 1}},
+                {"code": "FOOB-BARS-TEST", "expired": false, "expires_at": "", "sources": {"creator": 1, "submitter": 1, "lister": 1}}
+            ],
+            "sources": {}
+        }"#;
+        let (codes, warnings) = decode_codes(body, DecodeMode::Strict).unwrap();
+
+        assert_eq!(codes.codes.len(), 1);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code.as_deref(), Some("TOO-SHORT"));
+        assert_eq!(warnings[0].error.path.as_deref(), Some("codes[0].code"));
+        assert_eq!(crate::client::mapping_full(codes)[0].code, "FOOB-BARS-TEST");
+    }
+
+    #[test]
+    fn test_strict_accepts_valid() {
+        let body = r#"{"codes": [], "sources": {}}"#;
+        let (codes, warnings) = decode_codes(body, DecodeMode::Strict).unwrap();
+
+        assert!(codes.codes.is_empty());
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_lenient_skips_malformed_entries() {
+        let (codes, warnings) = decode_codes(BODY, DecodeMode::Lenient).unwrap();
+
+        assert_eq!(codes.codes.len(), 1);
+        assert!(
+            matches!(&codes.codes[0].code, ListedCode::Valid(code) if code == "FOOB-BARS-TEST")
+        );
+        assert_eq!(codes.sources.len(), 1);
+
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(warnings[0].code.as_deref(), Some("TOO-SHORT"));
+        assert_eq!(warnings[0].error.path.as_deref(), Some("codes[1].code"));
+        assert_eq!(warnings[1].code.as_deref(), Some("FOOB-BARS-TES2"));
+        assert_eq!(warnings[1].error.path.as_deref(), Some("codes[2].expired"));
+        assert_eq!(warnings[2].code, None);
+        assert_eq!(warnings[2].error.path.as_deref(), Some("sources.2.name"));
+    }
+
+    #[test]
+    fn test_lenient_fails_on_wrong_shape() {
+        assert!(matches!(
+            decode_codes(r#"{"codes": {}}"#, DecodeMode::Lenient),
+            Err(ClientError::Serde(_))
+        ));
+        assert!(matches!(
+            decode_codes("<html>", DecodeMode::Lenient),
+            Err(ClientError::Serde(_))
+        ));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::client::error::ClientError;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode, Url};
+use std::time::Duration;
+
+/// A hook observes, and may modify, the requests a `CodesClient` sends and the responses it receives.
+///
+/// Hooks are called for every attempt, so a retried request passes through them more than once.
+/// All methods do nothing by default.
+///
+/// ```
+/// use licc::client::{CodesClientBuilder, Hook};
+/// use reqwest::header::HeaderValue;
+///
+/// struct CorrelationId(&'static str);
+///
+/// impl Hook for CorrelationId {
+///     fn on_request(&self, request: &mut reqwest::Request, _attempt: u32) {
+///         request
+///             .headers_mut()
+///             .insert("x-correlation-id", HeaderValue::from_static(self.0));
+///     }
+/// }
+///
+/// let client = CodesClientBuilder::new()
+///     .hook(CorrelationId("abc"))
+///     .build()
+///     .unwrap();
+/// ```
+pub trait Hook: Send + Sync {
+    /// Called before a request is sent, `attempt` starts at 1.
+    /// Runs before the `Authenticator` of write requests, so request signatures cover any changes made here,
+    /// and credentials are not visible to the hook.
+    fn on_request(&self, _request: &mut reqwest::Request, _attempt: u32) {}
+
+    /// Called when the remote responded, before the response is checked for errors.
+    fn on_response(&self, _request: &RequestInfo, _response: &mut ResponseInfo) {}
+
+    /// Called when an attempt failed, also after `on_response` if the remote responded with an error.
+    fn on_error(&self, _request: &RequestInfo, _error: &ClientError) {}
+}
+
+/// The request an attempt sent, as passed to `Hook::on_response` and `Hook::on_error`.
+#[derive(Clone, Debug)]
+pub struct RequestInfo {
+    pub method: Method,
+    pub url: Url,
+    /// Starts at 1, and is increased for every retry
+    pub attempt: u32,
+    /// Time since the request was sent
+    pub elapsed: Duration,
+}
+
+/// A response of the remote, any changes made to it are seen by the client.
+#[derive(Clone, Debug)]
+pub struct ResponseInfo {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl RequestInfo {
+    pub(crate) fn new(request: &reqwest::Request, attempt: u32) -> Self {
+        Self {
+            method: request.method().clone(),
+            url: request.url().clone(),
+            attempt,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
@@ -0,0 +1,418 @@
+use crate::client::error::ClientError;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Upper bounds of the request latency buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Upper bounds of the buckets of the number of codes returned per call.
+const CODES_BUCKETS: [f64; 8] = [0.0, 1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0];
+
+/// Usage statistics of one or more `CodesClient`s, see `CodesClientBuilder::metrics`.
+///
+/// Clones share the same statistics, so a single `Metrics` can be shared between clients
+/// and read from elsewhere, e.g. to serve them to Prometheus:
+///
+/// ```
+/// use licc::client::{CodesClientBuilder, Metrics};
+///
+/// let metrics = Metrics::new();
+/// let client = CodesClientBuilder::new()
+///     .metrics(metrics.clone())
+///     .build()
+///     .unwrap();
+///
+/// let text = metrics.snapshot().to_prometheus();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Metrics(Arc<Mutex<MetricsSnapshot>>);
+
+/// The statistics recorded by `Metrics` at one point in time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricsSnapshot {
+    /// Requests sent to the remote, every retry counts as a request
+    pub requests: BTreeMap<RequestLabels, u64>,
+    /// How long the remote took to respond, by route
+    pub latency: BTreeMap<String, Histogram>,
+    /// Calls that failed, after any retries, by `ClientError` variant, e.g. `server_error` or `serde`.
+    /// Errors the client handles itself, such as a 404 from `get_source`, are not counted.
+    /// This includes failures that happen without a request, such as building a client with an invalid base URL.
+    pub errors: BTreeMap<&'static str, u64>,
+    pub retries: u64,
+    /// How often the client waited because the remote rate limited it
+    pub rate_limit_waits: u64,
+    /// Conditional requests answered from the `ResponseCache`
+    pub cache_hits: u64,
+    /// Requests with a `ResponseCache` configured that downloaded a fresh response
+    pub cache_misses: u64,
+    /// The number of codes returned by every `get_codes`, `get_codes_slim` or similar call
+    pub codes_returned: Histogram,
+}
+
+/// Identifies a kind of request, routes have codes and IDs replaced by `{code}` and `{id}`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RequestLabels {
+    pub method: String,
+    pub route: String,
+    /// None if the remote did not respond
+    pub status: Option<u16>,
+}
+
+/// Counts observations into buckets, like a Prometheus histogram.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    /// Upper bounds, and the number of observations less than or equal to them
+    pub buckets: Vec<(f64, u64)>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        Self {
+            buckets: bounds.iter().map(|bound| (*bound, 0)).collect(),
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in &mut self.buckets {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(MetricsSnapshot {
+            requests: BTreeMap::new(),
+            latency: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            retries: 0,
+            rate_limit_waits: 0,
+            cache_hits: 0,
+            cache_misses: 0,
+            codes_returned: Histogram::new(&CODES_BUCKETS),
+        })))
+    }
+
+    /// A copy of the statistics recorded so far.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.lock().clone()
+    }
+
+    /// Sets all statistics back to zero.
+    pub fn reset(&self) {
+        *self.lock() = Self::new().snapshot();
+    }
+
+    pub(crate) fn record_request(
+        &self,
+        method: &reqwest::Method,
+        route: &str,
+        status: Option<u16>,
+        latency: Duration,
+    ) {
+        let mut metrics = self.lock();
+
+        let labels = RequestLabels {
+            method: method.to_string(),
+            route: route.to_string(),
+            status,
+        };
+        *metrics.requests.entry(labels).or_default() += 1;
+
+        if status.is_some() {
+            metrics
+                .latency
+                .entry(route.to_string())
+                .or_insert_with(|| Histogram::new(&LATENCY_BUCKETS))
+                .observe(latency.as_secs_f64());
+        }
+    }
+
+    pub(crate) fn record_error(&self, err: &ClientError) {
+        *self.lock().errors.entry(error_kind(err)).or_default() += 1;
+    }
+
+    pub(crate) fn record_retry(&self) {
+        self.lock().retries += 1;
+    }
+
+    pub(crate) fn record_rate_limit_wait(&self) {
+        self.lock().rate_limit_waits += 1;
+    }
+
+    pub(crate) fn record_cache(&self, hit: bool) {
+        let mut metrics = self.lock();
+        match hit {
+            true => metrics.cache_hits += 1,
+            false => metrics.cache_misses += 1,
+        }
+    }
+
+    pub(crate) fn record_codes(&self, count: usize) {
+        self.lock().codes_returned.observe(count as f64);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MetricsSnapshot> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsSnapshot {
+    /// Renders the statistics in the Prometheus text exposition format, with metric names prefixed by `licc_`.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "licc_requests_total",
+            "counter",
+            "Requests sent to the remote.",
+        );
+        for (labels, count) in &self.requests {
+            let status = labels
+                .status
+                .map_or_else(|| "none".to_string(), |status| status.to_string());
+            let _ = writeln!(
+                out,
+                "licc_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                escape(&labels.method),
+                escape(&labels.route),
+                status,
+                count
+            );
+        }
+
+        header(
+            &mut out,
+            "licc_request_duration_seconds",
+            "histogram",
+            "Time until the remote responded.",
+        );
+        for (route, histogram) in &self.latency {
+            let labels = format!("route=\"{}\"", escape(route));
+            histogram_lines(
+                &mut out,
+                "licc_request_duration_seconds",
+                &labels,
+                histogram,
+            );
+        }
+
+        header(
+            &mut out,
+            "licc_errors_total",
+            "counter",
+            "Failed requests by kind of error.",
+        );
+        for (kind, count) in &self.errors {
+            let _ = writeln!(out, "licc_errors_total{{kind=\"{}\"}} {}", kind, count);
+        }
+
+        for (name, help, value) in [
+            ("licc_retries_total", "Retried requests.", self.retries),
+            (
+                "licc_rate_limit_waits_total",
+                "Waits because the remote rate limited the client.",
+                self.rate_limit_waits,
+            ),
+            (
+                "licc_cache_hits_total",
+                "Responses served from the response cache.",
+                self.cache_hits,
+            ),
+            (
+                "licc_cache_misses_total",
+                "Cacheable responses downloaded from the remote.",
+                self.cache_misses,
+            ),
+        ] {
+            header(&mut out, name, "counter", help);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+
+        header(
+            &mut out,
+            "licc_codes_returned",
+            "histogram",
+            "Codes returned per call.",
+        );
+        histogram_lines(&mut out, "licc_codes_returned", "", &self.codes_returned);
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn histogram_lines(out: &mut String, name: &str, labels: &str, histogram: &Histogram) {
+    let separator = if labels.is_empty() { "" } else { "," };
+
+    for (bound, count) in &histogram.buckets {
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"{}\"}} {}",
+            name, labels, separator, bound, count
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+        name, labels, separator, histogram.count
+    );
+
+    let labels = if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels)
+    };
+    let _ = writeln!(out, "{}_sum{} {}", name, labels, histogram.sum);
+    let _ = writeln!(out, "{}_count{} {}", name, labels, histogram.count);
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn error_kind(err: &ClientError) -> &'static str {
+    match err {
+        ClientError::Reqwest(_) => "reqwest",
+        ClientError::Serde(_) => "serde",
+        ClientError::ServerError(_) => "server_error",
+        #[cfg(feature = "write")]
+        ClientError::ApiKeyMissing => "api_key_missing",
+        ClientError::InvalidBaseUrl { .. } => "invalid_base_url",
+        ClientError::InvalidRateLimit { .. } => "invalid_rate_limit",
+        ClientError::RetriesExhausted { .. } => "retries_exhausted",
+        ClientError::RateLimited { .. } => "rate_limited",
+        ClientError::UnexpectedResponse { .. } => "unexpected_response",
+        ClientError::Authentication { .. } => "authentication",
+        ClientError::Unsupported { .. } => "unsupported",
+    }
+}
+
+/// The route of a request to `url`, relative to `base_url`, with codes and IDs replaced
+/// so that every code does not become a route of its own.
+pub(crate) fn route(base_url: &str, url: &reqwest::Url) -> String {
+    let path = url
+        .as_str()
+        .strip_prefix(base_url)
+        .map(|path| path.split(['?', '#']).next().unwrap_or_default())
+        .unwrap_or_else(|| url.path());
+
+    let mut route = String::new();
+    let mut previous = "";
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        let segment = if segment.chars().all(|c| c.is_ascii_digit()) {
+            "{id}"
+        } else if previous == "codes" && segment != "batch" {
+            "{code}"
+        } else {
+            segment
+        };
+
+        route.push('/');
+        route.push_str(segment);
+        previous = segment;
+    }
+
+    if route.is_empty() {
+        route.push('/');
+    }
+    route
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        let base_url = "http://localhost:8000/v1";
+        let route = |url: &str| route(base_url, &reqwest::Url::parse(url).unwrap());
+
+        assert_eq!(route("http://localhost:8000/v1/codes"), "/codes");
+        assert_eq!(
+            route("http://localhost:8000/v1/codes/FOOB-BARS-TEST/expire"),
+            "/codes/{code}/expire"
+        );
+        assert_eq!(
+            route("http://localhost:8000/v1/codes/batch"),
+            "/codes/batch"
+        );
+        assert_eq!(
+            route("http://localhost:8000/v1/sources/12/merge?foo=bar"),
+            "/sources/{id}/merge"
+        );
+        assert_eq!(route("http://other.example/v2/sources"), "/v2/sources");
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new(&[1.0, 5.0]);
+        histogram.observe(1.0);
+        histogram.observe(3.0);
+        histogram.observe(10.0);
+
+        assert_eq!(histogram.buckets, vec![(1.0, 1), (5.0, 2)]);
+        assert_eq!(histogram.count, 3);
+        assert_eq!(histogram.sum, 14.0);
+    }
+
+    #[test]
+    fn test_to_prometheus() {
+        let metrics = Metrics::new();
+        metrics.record_request(
+            &reqwest::Method::GET,
+            "/codes",
+            Some(200),
+            Duration::from_millis(20),
+        );
+        metrics.record_request(&reqwest::Method::GET, "/codes", None, Duration::ZERO);
+        metrics.record_error(&ClientError::RateLimited { retry_after: None });
+        metrics.record_cache(true);
+        metrics.record_codes(3);
+
+        let text = metrics.clone().snapshot().to_prometheus();
+
+        assert!(text
+            .contains("licc_requests_total{method=\"GET\",route=\"/codes\",status=\"200\"} 1\n"));
+        assert!(text
+            .contains("licc_requests_total{method=\"GET\",route=\"/codes\",status=\"none\"} 1\n"));
+        assert!(text
+            .contains("licc_request_duration_seconds_bucket{route=\"/codes\",le=\"0.025\"} 1\n"));
+        assert!(text.contains("licc_request_duration_seconds_count{route=\"/codes\"} 1\n"));
+        assert!(text.contains("licc_errors_total{kind=\"rate_limited\"} 1\n"));
+        assert!(text.contains("licc_cache_hits_total 1\n"));
+        assert!(text.contains("licc_codes_returned_bucket{le=\"5\"} 1\n"));
+        assert!(text.contains("licc_codes_returned_sum 3\n"));
+
+        metrics.reset();
+        assert!(metrics.snapshot().requests.is_empty());
+    }
+}
@@ -0,0 +1,486 @@
+use crate::api::CodesApi;
+use crate::client::error::ClientError;
+use crate::client::CodesClient;
+#[cfg(feature = "write")]
+use crate::write;
+use crate::{ChestCode, Code, Source, SourceIndex};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// How long a mirror is skipped after it failed, unless all mirrors failed.
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// A response, and the base URL of the mirror that served it.
+#[derive(Debug)]
+pub struct Served<T> {
+    pub value: T,
+    pub mirror: String,
+}
+
+/// The codes of several mirrors, merged by `MirroredClient::get_codes_merged`.
+#[derive(Debug)]
+pub struct Merged {
+    /// Every code listed by any mirror, once, in the order the mirrors list them,
+    /// with the mirror that listed it first. The IDs of its sources are those of that mirror.
+    pub codes: Vec<Served<Code>>,
+    /// The base URLs of the mirrors that responded
+    pub mirrors: Vec<String>,
+    /// The mirrors that failed, and why
+    pub failures: Vec<(String, ClientError)>,
+}
+
+/// The health of a mirror as seen by the `MirroredClient`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MirrorStatus {
+    pub base_url: String,
+    /// False while the mirror is skipped because it recently failed
+    pub healthy: bool,
+    /// How often the mirror failed since it last responded
+    pub consecutive_failures: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Health {
+    consecutive_failures: u32,
+    unhealthy_until: Option<Instant>,
+}
+
+/// MirroredClient sends requests to the first healthy mirror of a list of codes API deployments,
+/// and falls back on the next one when a mirror cannot be reached or responds with a server error.
+///
+/// A mirror that failed is skipped for a while, all mirrors are tried before giving up.
+/// Clones share the health of the mirrors.
+#[derive(Clone)]
+pub struct MirroredClient {
+    mirrors: Vec<CodesClient>,
+    health: Arc<Mutex<Vec<Health>>>,
+    cooldown: Duration,
+}
+
+impl MirroredClient {
+    /// Creates a client for `primary` and each of `others`, which are validated as in `CodesClientBuilder::base_url`.
+    /// Use `from_clients` to configure the clients further.
+    pub fn new(
+        primary: impl Into<String>,
+        others: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Self, ClientError> {
+        let client = |base_url: String| CodesClient::builder().base_url(base_url).build();
+
+        let primary = client(primary.into())?;
+        let others = others
+            .into_iter()
+            .map(|base_url| client(base_url.into()))
+            .collect::<Result<Vec<CodesClient>, ClientError>>()?;
+
+        Ok(Self::from_clients(primary, others))
+    }
+
+    /// Uses `primary` and then `others` as mirrors, in order of preference.
+    pub fn from_clients(
+        primary: CodesClient,
+        others: impl IntoIterator<Item = CodesClient>,
+    ) -> Self {
+        let mirrors: Vec<CodesClient> = std::iter::once(primary).chain(others).collect();
+
+        Self {
+            health: Arc::new(Mutex::new(vec![Health::default(); mirrors.len()])),
+            mirrors,
+            cooldown: DEFAULT_COOLDOWN,
+        }
+    }
+
+    /// How long a mirror is skipped after it failed, defaults to 30 seconds.
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// The health of every mirror, in order of preference.
+    pub fn status(&self) -> Vec<MirrorStatus> {
+        let now = Instant::now();
+
+        self.mirrors
+            .iter()
+            .zip(self.health().iter())
+            .map(|(mirror, health)| MirrorStatus {
+                base_url: mirror.base_url().to_string(),
+                healthy: health.unhealthy_until.is_none_or(|until| until <= now),
+                consecutive_failures: health.consecutive_failures,
+            })
+            .collect()
+    }
+
+    /// Query HTTP GET `/api/v1/codes` on the first mirror that responds, see `CodesClient::get_codes`.
+    pub async fn get_codes(&self) -> Result<Served<Vec<Code>>, ClientError> {
+        self.failover(should_fail_over, |mirror| async move {
+            mirror.get_codes().await
+        })
+        .await
+    }
+
+    /// Query HTTP GET `/api/v1/codes` on the first mirror that responds, see `CodesClient::get_codes_slim`.
+    pub async fn get_codes_slim(&self) -> Result<Served<Vec<Code>>, ClientError> {
+        self.failover(should_fail_over, |mirror| async move {
+            mirror.get_codes_slim().await
+        })
+        .await
+    }
+
+    /// Query HTTP GET `/api/v1/sources` on the first mirror that responds, see `CodesClient::get_sources`.
+    pub async fn get_sources(&self) -> Result<Served<SourceIndex>, ClientError> {
+        self.failover(should_fail_over, |mirror| async move {
+            mirror.get_sources().await
+        })
+        .await
+    }
+
+    /// Query HTTP GET `/api/v1/codes` on every mirror at the same time, and merge the results.
+    ///
+    /// Codes listed by several mirrors are returned once. The first mirror to list a code decides its data,
+    /// later mirrors only fill in what it did not know, such as an unknown creator.
+    /// Source IDs are local to each mirror, so a source is only filled in if the first mirror
+    /// knows a source with the same name and URL, and then has the ID of the first mirror.
+    /// Fails only if every mirror failed.
+    pub async fn get_codes_merged(&self) -> Result<Merged, ClientError> {
+        let responses =
+            futures_util::future::join_all(self.mirrors.iter().enumerate().map(
+                |(index, mirror)| async move { (index, mirror.get_codes_with_sources().await) },
+            ))
+            .await;
+
+        let mut merged = Merged {
+            codes: Vec::new(),
+            mirrors: Vec::new(),
+            failures: Vec::new(),
+        };
+        let mut positions: HashMap<ChestCode, usize> = HashMap::new();
+        let mut sources: HashMap<String, SourceIndex> = HashMap::new();
+
+        for (index, response) in responses {
+            let base_url = self.mirrors[index].base_url().to_string();
+            let codes = match response {
+                Ok((codes, index_sources)) => {
+                    self.record(index, true);
+                    sources.insert(base_url.clone(), index_sources);
+                    codes
+                }
+                Err(err) => {
+                    self.record(index, !should_fail_over(&err));
+                    merged.failures.push((base_url, err));
+                    continue;
+                }
+            };
+
+            for code in codes {
+                match positions.get(&code.code) {
+                    Some(position) => {
+                        let served = &mut merged.codes[*position];
+                        fill_in(&mut served.value, &sources[&served.mirror], code);
+                    }
+                    None => {
+                        positions.insert(code.code.clone(), merged.codes.len());
+                        merged.codes.push(Served {
+                            value: code,
+                            mirror: base_url.clone(),
+                        });
+                    }
+                }
+            }
+            merged.mirrors.push(base_url);
+        }
+
+        if merged.mirrors.is_empty() {
+            let (_, last) = merged.failures.pop().expect("there is at least one mirror");
+            return Err(ClientError::RetriesExhausted {
+                attempts: self.mirrors.len() as u32,
+                last: Box::new(last),
+            });
+        }
+
+        Ok(merged)
+    }
+
+    /// Query HTTP PUT `/api/v1/codes` on the first mirror that can be reached, see `CodesClient::insert_code`.
+    /// *This requires an API Key.*
+    ///
+    /// Falls back only if the mirror could not be reached, as it may have inserted the code despite failing.
+    #[cfg(feature = "write")]
+    pub async fn insert_code(
+        &self,
+        insert_request: write::InsertCodeRequest,
+    ) -> Result<Served<Option<i32>>, ClientError> {
+        self.failover(is_unreachable, |mirror| {
+            let insert_request = insert_request.clone();
+            async move { mirror.insert_code(insert_request).await }
+        })
+        .await
+    }
+
+    /// Sends the request to each mirror in turn, healthy mirrors first, until one responds
+    /// or fails in a way that `fail_over` does not accept.
+    async fn failover<'a, T, F, Fut>(
+        &'a self,
+        fail_over: fn(&ClientError) -> bool,
+        request: F,
+    ) -> Result<Served<T>, ClientError>
+    where
+        F: Fn(&'a CodesClient) -> Fut,
+        Fut: Future<Output = Result<T, ClientError>>,
+    {
+        let order = self.order();
+        let mut attempts = 0;
+        let mut last = None;
+
+        for index in order {
+            attempts += 1;
+            match request(&self.mirrors[index]).await {
+                Ok(value) => {
+                    self.record(index, true);
+                    return Ok(Served {
+                        value,
+                        mirror: self.mirrors[index].base_url().to_string(),
+                    });
+                }
+                Err(err) if fail_over(&err) => {
+                    self.record(index, false);
+                    last = Some(err);
+                }
+                Err(err) => {
+                    self.record(index, true);
+                    return Err(err);
+                }
+            }
+        }
+
+        let last = last.expect("there is at least one mirror");
+        Err(match attempts {
+            1 => last,
+            attempts => ClientError::RetriesExhausted {
+                attempts,
+                last: Box::new(last),
+            },
+        })
+    }
+
+    /// The indexes of the mirrors, healthy mirrors first, each in order of preference.
+    fn order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let health = self.health();
+
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) =
+            (0..self.mirrors.len()).partition(|index| {
+                health[*index]
+                    .unhealthy_until
+                    .is_none_or(|until| until <= now)
+            });
+        healthy.extend(unhealthy);
+
+        healthy
+    }
+
+    /// Records whether the mirror responded, which includes responding with an error that is not its fault.
+    fn record(&self, index: usize, responded: bool) {
+        let mut health = self.health();
+        let health = &mut health[index];
+
+        if responded {
+            *health = Health::default();
+        } else {
+            health.consecutive_failures += 1;
+            health.unhealthy_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+
+    fn health(&self) -> MutexGuard<'_, Vec<Health>> {
+        self.health
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Whether another mirror may respond where this one failed: it could not be reached or had a server error.
+fn should_fail_over(err: &ClientError) -> bool {
+    match err {
+        ClientError::Reqwest(err) => err.is_connect() || err.is_timeout(),
+        ClientError::RetriesExhausted { last, .. } => should_fail_over(last),
+        _ => err
+            .status_code()
+            .is_some_and(|status| status.is_server_error()),
+    }
+}
+
+#[cfg(feature = "write")]
+fn is_unreachable(err: &ClientError) -> bool {
+    match err {
+        ClientError::Reqwest(err) => err.is_connect(),
+        ClientError::RetriesExhausted { last, .. } => is_unreachable(last),
+        _ => false,
+    }
+}
+
+/// Fills in the data `code` is missing from `other`, a listing of the same code by another mirror.
+/// Sources are looked up by name and URL in `sources`, the sources of the mirror that listed `code`.
+fn fill_in(code: &mut Code, sources: &SourceIndex, other: Code) {
+    let local = |source: Option<Source>| {
+        let source = source?;
+        sources
+            .iter()
+            .find(|local| local.name == source.name && local.url == source.url)
+            .cloned()
+    };
+
+    if code.expires_at.is_none() {
+        code.expires_at = other.expires_at;
+    }
+    if code.creator.is_none() {
+        code.creator = local(other.creator);
+    }
+    if code.submitter.is_none() {
+        code.submitter = local(other.submitter);
+    }
+    if code.lister.is_none() {
+        code.lister = local(other.lister);
+    }
+}
+
+#[async_trait]
+impl CodesApi for MirroredClient {
+    async fn get_codes(&self) -> Result<Vec<Code>, ClientError> {
+        Ok(MirroredClient::get_codes(self).await?.value)
+    }
+
+    async fn get_codes_slim(&self) -> Result<Vec<Code>, ClientError> {
+        Ok(MirroredClient::get_codes_slim(self).await?.value)
+    }
+
+    async fn get_sources(&self) -> Result<SourceIndex, ClientError> {
+        Ok(MirroredClient::get_sources(self).await?.value)
+    }
+
+    #[cfg(feature = "write")]
+    async fn insert_code(
+        &self,
+        insert_request: write::InsertCodeRequest,
+    ) -> Result<Option<i32>, ClientError> {
+        Ok(MirroredClient::insert_code(self, insert_request)
+            .await?
+            .value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn source(id: i32, name: &str) -> Source {
+        Source {
+            id,
+            name: name.to_string(),
+            url: "https://foo.example".to_string(),
+        }
+    }
+
+    fn code(code: &str, creator: Option<Source>) -> Code {
+        Code {
+            code: code.parse().unwrap(),
+            expired: false,
+            expires_at: None,
+            creator,
+            submitter: None,
+            lister: None,
+        }
+    }
+
+    fn client() -> MirroredClient {
+        MirroredClient::new("http://127.0.0.1:9/v1", ["http://127.0.0.1:10/v1"]).unwrap()
+    }
+
+    #[test]
+    fn test_invalid_mirror() {
+        assert!(MirroredClient::new("not a url", Vec::<String>::new()).is_err());
+        assert!(MirroredClient::new("http://127.0.0.1:9/v1", ["not a url"]).is_err());
+        assert_eq!(
+            MirroredClient::new("http://127.0.0.1:9/v1", Vec::<String>::new())
+                .unwrap()
+                .status()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_health() {
+        let client = client();
+        assert_eq!(client.order(), vec![0, 1]);
+
+        client.record(0, false);
+        assert_eq!(client.order(), vec![1, 0]);
+        assert!(!client.status()[0].healthy);
+        assert_eq!(client.status()[0].consecutive_failures, 1);
+        assert!(!client.clone().status()[0].healthy);
+
+        client.record(0, true);
+        assert_eq!(client.order(), vec![0, 1]);
+        assert_eq!(client.status()[0].consecutive_failures, 0);
+    }
+
+    #[test]
+    fn test_should_fail_over() {
+        let unexpected = |status| ClientError::UnexpectedResponse {
+            status,
+            body: String::new(),
+        };
+
+        assert!(should_fail_over(&unexpected(
+            reqwest::StatusCode::BAD_GATEWAY
+        )));
+        assert!(!should_fail_over(&unexpected(
+            reqwest::StatusCode::NOT_FOUND
+        )));
+        assert!(!should_fail_over(&ClientError::RateLimited {
+            retry_after: None
+        }));
+    }
+
+    #[test]
+    fn test_fill_in() {
+        let sources: SourceIndex = [source(7, "foo"), source(8, "bar")].into_iter().collect();
+
+        let mut merged = code("FOOB-BARS-TEST", None);
+        fill_in(
+            &mut merged,
+            &sources,
+            code("FOOB-BARS-TEST", Some(source(2, "baz"))),
+        );
+        assert!(merged.creator.is_none());
+
+        fill_in(
+            &mut merged,
+            &sources,
+            code("FOOB-BARS-TEST", Some(source(3, "foo"))),
+        );
+        assert_eq!(merged.creator.as_ref().unwrap().id, 7);
+
+        fill_in(
+            &mut merged,
+            &sources,
+            code("FOOB-BARS-TEST", Some(source(4, "bar"))),
+        );
+        assert_eq!(merged.creator.unwrap().id, 7);
+    }
+
+    #[tokio::test]
+    async fn test_all_mirrors_unreachable() {
+        let client = client();
+
+        assert!(matches!(
+            client.get_codes().await,
+            Err(ClientError::RetriesExhausted { attempts: 2, .. })
+        ));
+        assert!(client.status().iter().all(|status| !status.healthy));
+        assert!(client.get_codes_merged().await.is_err());
+    }
+}
@@ -0,0 +1,393 @@
+use crate::Code;
+
+/// Which codes `CodesClient::query_codes` returns. All filters are optional and combined.
+///
+/// The filters and pagination are sent to the remote if it supports them, see `Capability`,
+/// otherwise the full list of codes is fetched and the query is applied to it by the client.
+///
+/// ```
+/// use licc::client::CodesQuery;
+///
+/// let query = CodesQuery::new().active().creator_name("Liefland").limit(50);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CodesQuery {
+    pub(crate) expired: Option<bool>,
+    pub(crate) since: Option<String>,
+    pub(crate) creator: Option<i32>,
+    pub(crate) creator_name: Option<String>,
+    pub(crate) lister: Option<i32>,
+    pub(crate) limit: Option<u32>,
+    pub(crate) offset: Option<u32>,
+    pub(crate) cursor: Option<String>,
+}
+
+/// One page of the results of a `CodesQuery`.
+#[derive(Debug)]
+pub struct CodesPage {
+    pub codes: Vec<Code>,
+    /// The query for the next page, None if this is the last page
+    pub next: Option<CodesQuery>,
+}
+
+impl CodesQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only codes that have not expired.
+    pub fn active(mut self) -> Self {
+        self.expired = Some(false);
+        self
+    }
+
+    /// Only codes that have expired.
+    pub fn expired(mut self) -> Self {
+        self.expired = Some(true);
+        self
+    }
+
+    /// Only codes that were still valid at `since`, i.e. that expire at or after it.
+    /// `since` is in the format of `Code::expires_at`, e.g. `2024-01-01 00:00:00.0`.
+    /// Codes without a known expiry are included unless they expired.
+    /// Without the `chrono` feature, timestamps are compared as text when the client applies the query.
+    pub fn since(mut self, since: impl Into<String>) -> Self {
+        self.since = Some(since.into());
+        self
+    }
+
+    /// Like `since`, taking a `DateTime<Utc>`.
+    #[cfg(feature = "chrono")]
+    pub fn since_datetime(self, since: chrono::DateTime<chrono::Utc>) -> Self {
+        self.since(since.to_rfc3339())
+    }
+
+    /// Only codes created by the source with this ID.
+    pub fn creator(mut self, id: i32) -> Self {
+        self.creator = Some(id);
+        self
+    }
+
+    /// Only codes created by a source with this name, compared case-insensitively.
+    pub fn creator_name(mut self, name: impl Into<String>) -> Self {
+        self.creator_name = Some(name.into());
+        self
+    }
+
+    /// Only codes listed by the source with this ID.
+    pub fn lister(mut self, id: i32) -> Self {
+        self.lister = Some(id);
+        self
+    }
+
+    /// Return at most `limit` codes per page.
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Skip the first `offset` matching codes.
+    pub fn offset(mut self, offset: u32) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Continue after a page, as returned by the remote. Takes precedence over `offset`.
+    pub fn cursor(mut self, cursor: impl Into<String>) -> Self {
+        self.cursor = Some(cursor.into());
+        self
+    }
+
+    /// Whether `code` passes the filters of this query, pagination aside.
+    pub fn matches(&self, code: &Code) -> bool {
+        if self.expired.is_some_and(|expired| code.expired != expired) {
+            return false;
+        }
+
+        if let Some(since) = &self.since {
+            let valid = match &code.expires_at {
+                Some(expires_at) => expires_since(expires_at, since),
+                None => !code.expired,
+            };
+            if !valid {
+                return false;
+            }
+        }
+
+        let source_id = |source: &Option<crate::Source>| source.as_ref().map(|source| source.id);
+
+        if self.creator.is_some() && source_id(&code.creator) != self.creator {
+            return false;
+        }
+
+        if let Some(name) = &self.creator_name {
+            let matches = code
+                .creator
+                .as_ref()
+                .is_some_and(|creator| creator.name.eq_ignore_ascii_case(name));
+            if !matches {
+                return false;
+            }
+        }
+
+        if self.lister.is_some() && source_id(&code.lister) != self.lister {
+            return false;
+        }
+
+        true
+    }
+
+    pub(crate) fn has_filters(&self) -> bool {
+        self.expired.is_some()
+            || self.since.is_some()
+            || self.creator.is_some()
+            || self.creator_name.is_some()
+            || self.lister.is_some()
+    }
+
+    /// The query string of the filters, and of the pagination if `paginate` is set.
+    pub(crate) fn query_string(&self, filter: bool, paginate: bool) -> String {
+        let mut params: Vec<(&str, String)> = Vec::new();
+
+        if filter {
+            if let Some(expired) = self.expired {
+                params.push(("expired", expired.to_string()));
+            }
+            if let Some(since) = &self.since {
+                params.push(("since", since.clone()));
+            }
+            if let Some(creator) = self.creator {
+                params.push(("creator", creator.to_string()));
+            }
+            if let Some(name) = &self.creator_name {
+                params.push(("creator_name", name.clone()));
+            }
+            if let Some(lister) = self.lister {
+                params.push(("lister", lister.to_string()));
+            }
+        }
+
+        if paginate {
+            if let Some(limit) = self.limit {
+                params.push(("limit", limit.to_string()));
+            }
+            match &self.cursor {
+                Some(cursor) => params.push(("cursor", cursor.clone())),
+                None => {
+                    if let Some(offset) = self.offset {
+                        params.push(("offset", offset.to_string()));
+                    }
+                }
+            }
+        }
+
+        params
+            .into_iter()
+            .map(|(name, value)| format!("{}={}", name, encode(&value)))
+            .collect::<Vec<String>>()
+            .join("&")
+    }
+
+    /// Applies the query to the full list of codes, for remotes that do not support it.
+    pub(crate) fn apply(&self, codes: Vec<Code>) -> CodesPage {
+        let offset = self.offset.unwrap_or(0);
+        let mut matching = codes
+            .into_iter()
+            .filter(|code| self.matches(code))
+            .skip(offset as usize);
+
+        let codes: Vec<Code> = match self.limit {
+            Some(limit) => matching.by_ref().take(limit as usize).collect(),
+            None => matching.by_ref().collect(),
+        };
+
+        let next = match (self.limit, matching.next()) {
+            (Some(limit), Some(_)) => offset.checked_add(limit).map(|next| self.next_offset(next)),
+            _ => None,
+        };
+
+        CodesPage { codes, next }
+    }
+
+    /// The query for the page after a page the remote returned.
+    pub(crate) fn next_page(&self, returned: usize, cursor: Option<String>) -> Option<CodesQuery> {
+        if let Some(cursor) = cursor {
+            return Some(self.clone().cursor(cursor));
+        }
+
+        match self.limit {
+            Some(limit) if self.cursor.is_none() && returned >= limit as usize => self
+                .offset
+                .unwrap_or(0)
+                .checked_add(limit)
+                .map(|next| self.next_offset(next)),
+            _ => None,
+        }
+    }
+
+    fn next_offset(&self, offset: u32) -> CodesQuery {
+        let mut next = self.clone().offset(offset);
+        next.cursor = None;
+        next
+    }
+}
+
+/// Compares the timestamps as points in time if both can be parsed, as text otherwise.
+#[cfg(feature = "chrono")]
+fn expires_since(expires_at: &str, since: &str) -> bool {
+    use crate::expiry::parse_timestamp;
+
+    match (parse_timestamp(expires_at), parse_timestamp(since)) {
+        (Ok(expires_at), Ok(since)) => expires_at >= since,
+        _ => expires_at >= since,
+    }
+}
+
+#[cfg(not(feature = "chrono"))]
+fn expires_since(expires_at: &str, since: &str) -> bool {
+    expires_at >= since
+}
+
+/// Percent-encodes anything but unreserved characters.
+fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Source;
+
+    fn code(code: &str, expired: bool, creator: i32) -> Code {
+        Code {
+            code: code.parse().unwrap(),
+            expired,
+            expires_at: None,
+            creator: Some(Source {
+                id: creator,
+                name: format!("Creator {}", creator),
+                url: "https://creator.example".to_string(),
+            }),
+            submitter: None,
+            lister: None,
+        }
+    }
+
+    fn codes() -> Vec<Code> {
+        vec![
+            code("AAAA-AAAA-AAAA", false, 1),
+            code("BBBB-BBBB-BBBB", true, 1),
+            code("CCCC-CCCC-CCCC", false, 2),
+            code("DDDD-DDDD-DDDD", false, 1),
+        ]
+    }
+
+    #[test]
+    fn test_matches() {
+        let code = code("AAAA-AAAA-AAAA", false, 1);
+
+        assert!(CodesQuery::new().matches(&code));
+        assert!(CodesQuery::new().active().creator(1).matches(&code));
+        assert!(CodesQuery::new().creator_name("creator 1").matches(&code));
+        assert!(!CodesQuery::new().expired().matches(&code));
+        assert!(!CodesQuery::new().creator(2).matches(&code));
+        assert!(!CodesQuery::new().lister(1).matches(&code));
+    }
+
+    #[test]
+    fn test_matches_since() {
+        let mut code = code("AAAA-AAAA-AAAA", false, 1);
+        code.expires_at = Some("2024-01-02 00:00:00.0".to_string());
+
+        assert!(CodesQuery::new()
+            .since("2024-01-01 00:00:00.0")
+            .matches(&code));
+        assert!(!CodesQuery::new()
+            .since("2024-01-03 00:00:00.0")
+            .matches(&code));
+
+        code.expires_at = None;
+        assert!(CodesQuery::new()
+            .since("2024-01-03 00:00:00.0")
+            .matches(&code));
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn test_matches_since_datetime() {
+        use chrono::{TimeZone, Utc};
+
+        let mut code = code("AAAA-AAAA-AAAA", false, 1);
+        code.expires_at = Some("2024-01-02 00:00:00.0".to_string());
+
+        let since = |day| {
+            CodesQuery::new().since_datetime(Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap())
+        };
+        assert!(since(2).matches(&code));
+        assert!(!since(3).matches(&code));
+    }
+
+    #[test]
+    fn test_query_string() {
+        let query = CodesQuery::new()
+            .active()
+            .creator_name("Foo & Bar")
+            .limit(10)
+            .offset(20);
+
+        assert_eq!(
+            query.query_string(true, true),
+            "expired=false&creator_name=Foo%20%26%20Bar&limit=10&offset=20"
+        );
+        assert_eq!(
+            query.query_string(true, false),
+            "expired=false&creator_name=Foo%20%26%20Bar"
+        );
+        assert_eq!(
+            query.clone().cursor("abc").query_string(false, true),
+            "limit=10&cursor=abc"
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let query = CodesQuery::new().active().creator(1).limit(1);
+
+        let page = query.apply(codes());
+        assert_eq!(page.codes.len(), 1);
+        assert_eq!(page.codes[0].code, "AAAA-AAAA-AAAA");
+
+        let page = query.apply(codes());
+        let page = page.next.unwrap().apply(codes());
+        assert_eq!(page.codes[0].code, "DDDD-DDDD-DDDD");
+        assert!(page.next.is_none());
+
+        assert_eq!(CodesQuery::new().apply(codes()).codes.len(), 4);
+    }
+
+    #[test]
+    fn test_next_page() {
+        let query = CodesQuery::new().limit(2);
+
+        assert_eq!(
+            query.next_page(2, None),
+            Some(CodesQuery::new().limit(2).offset(2))
+        );
+        assert_eq!(query.next_page(1, None), None);
+        assert_eq!(query.clone().offset(u32::MAX - 1).next_page(2, None), None);
+        assert_eq!(
+            query.next_page(1, Some("abc".to_string())),
+            Some(CodesQuery::new().limit(2).cursor("abc"))
+        );
+    }
+}
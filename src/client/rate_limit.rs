@@ -0,0 +1,211 @@
+use crate::client::error::ClientError;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// About 30 years, a pause that is practically forever but fits in an `Instant` on every platform.
+const FAR_FUTURE: Duration = Duration::from_secs(86400 * 365 * 30);
+
+/// A token bucket limiting how many requests are sent to the remote.
+///
+/// The bucket holds up to `burst` tokens and refills at `requests_per_second`,
+/// every request takes one token and waits for it if none are available.
+///
+/// Clones share the same bucket, so a limiter (or a `CodesClient` using one) can be cloned
+/// into multiple tasks while respecting a single limit.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    tokens: f64,
+    per_second: f64,
+    updated_at: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Allow on average `requests_per_second` requests, with up to `burst` requests at once.
+    ///
+    /// # Panics
+    ///
+    /// If `requests_per_second` is not a positive, finite number, see `try_new`.
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        match Self::try_new(requests_per_second, burst) {
+            Ok(limiter) => limiter,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Like `new`, but fails with `ClientError::InvalidRateLimit` if `requests_per_second`
+    /// is not a positive, finite number.
+    pub fn try_new(requests_per_second: f64, burst: u32) -> Result<Self, ClientError> {
+        if !requests_per_second.is_finite() || requests_per_second <= 0.0 {
+            return Err(ClientError::InvalidRateLimit {
+                requests_per_second,
+            });
+        }
+        let capacity = f64::from(burst.max(1));
+
+        Ok(Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                capacity,
+                tokens: capacity,
+                per_second: requests_per_second,
+                updated_at: Instant::now(),
+                paused_until: None,
+            })),
+        })
+    }
+
+    /// Wait until a request may be sent.
+    pub async fn acquire(&self) {
+        let wait = self.bucket().reserve(Instant::now());
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Hold back all requests for the given duration, e.g. because the remote responded with 429.
+    /// Durations too long to represent as an `Instant` pause for as long as one can represent.
+    pub fn pause_for(&self, duration: Duration) {
+        let now = Instant::now();
+        let until = now
+            .checked_add(duration)
+            .or_else(|| now.checked_add(FAR_FUTURE))
+            .unwrap_or(now);
+
+        self.bucket().pause_until(until);
+    }
+
+    fn bucket(&self) -> std::sync::MutexGuard<'_, Bucket> {
+        // The bucket holds no invariants a panic could break, so a poisoned lock is still usable.
+        self.bucket.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Bucket {
+    /// Takes a token and returns how long to wait until that token is actually available.
+    /// Tokens may go negative, which queues the callers behind each other.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.updated_at = now;
+        self.tokens -= 1.0;
+
+        let mut wait = if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            // Very small rates can ask for a wait longer than a Duration can hold.
+            Duration::try_from_secs_f64(-self.tokens / self.per_second).unwrap_or(Duration::MAX)
+        };
+
+        if let Some(until) = self.paused_until {
+            wait = wait.max(until.saturating_duration_since(now));
+        }
+
+        wait
+    }
+
+    fn pause_until(&mut self, until: Instant) {
+        if self.paused_until.is_none_or(|current| current < until) {
+            self.paused_until = Some(until);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_burst_is_free() {
+        let limiter = RateLimiter::new(1.0, 3);
+        let now = Instant::now();
+        let mut bucket = limiter.bucket();
+
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::from_secs(1));
+        assert_eq!(bucket.reserve(now), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_refills_over_time() {
+        let limiter = RateLimiter::new(2.0, 1);
+        let now = Instant::now();
+        let mut bucket = limiter.bucket();
+
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::from_millis(500));
+        assert_eq!(
+            bucket.reserve(now + Duration::from_secs(10)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_clones_share_bucket() {
+        let limiter = RateLimiter::new(1.0, 1);
+        let clone = limiter.clone();
+        let now = Instant::now();
+
+        assert_eq!(limiter.bucket().reserve(now), Duration::ZERO);
+        assert_eq!(clone.bucket().reserve(now), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_pause() {
+        let limiter = RateLimiter::new(100.0, 10);
+        let now = Instant::now();
+        let mut bucket = limiter.bucket();
+
+        bucket.pause_until(now + Duration::from_secs(5));
+        bucket.pause_until(now + Duration::from_secs(1));
+
+        assert_eq!(bucket.reserve(now), Duration::from_secs(5));
+        assert_eq!(bucket.reserve(now + Duration::from_secs(6)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_tiny_rate() {
+        let limiter = RateLimiter::new(f64::MIN_POSITIVE, 1);
+        let now = Instant::now();
+        let mut bucket = limiter.bucket();
+
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::MAX);
+    }
+
+    #[test]
+    fn test_invalid_rate() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(
+                matches!(
+                    RateLimiter::try_new(rate, 1),
+                    Err(ClientError::InvalidRateLimit { .. })
+                ),
+                "{} should be rejected",
+                rate
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "must be a positive, finite number")]
+    fn test_new_panics_on_invalid_rate() {
+        RateLimiter::new(0.0, 1);
+    }
+
+    #[test]
+    fn test_pause_for_max() {
+        let limiter = RateLimiter::new(1.0, 1);
+        limiter.pause_for(Duration::MAX);
+
+        assert!(limiter.bucket().reserve(Instant::now()) > Duration::from_secs(86400 * 365));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::client::error::ClientError;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::time::{Duration, SystemTime};
+
+/// RetryPolicy decides whether, and after how long, a failed request is attempted again.
+///
+/// Only transient failures are retried: connection errors, timeouts and
+/// 408, 500, 502, 503 and 504 responses.
+/// GET and DELETE requests are retried by default, PUT, POST and PATCH requests only when enabled
+/// through `retry_writes`, as retrying a write that reached the remote may apply it twice.
+///
+/// The default policy does not retry at all.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    respect_retry_after: bool,
+    max_retry_after: Duration,
+    retry_writes: bool,
+}
+
+impl RetryPolicy {
+    /// A policy that never retries, every request is attempted exactly once.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+            jitter: true,
+            respect_retry_after: true,
+            max_retry_after: Duration::from_secs(60),
+            retry_writes: false,
+        }
+    }
+
+    /// A policy that attempts a request at most `max_attempts` times (including the first attempt),
+    /// doubling the delay between attempts starting from 200ms up to 10 seconds.
+    pub fn exponential(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..Self::none()
+        }
+    }
+
+    /// The delay before the first retry, each following retry doubles it.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// The upper bound of the computed delay between two attempts.
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Randomize the delay to somewhere between half and the full computed backoff,
+    /// so that many clients failing at once do not retry in lockstep. Enabled by default.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Wait for as long as the remote asks in its `Retry-After` header, if present. Enabled by default.
+    pub fn respect_retry_after(mut self, respect: bool) -> Self {
+        self.respect_retry_after = respect;
+        self
+    }
+
+    /// The longest `Retry-After` the client waits for, 60 seconds by default.
+    /// If the remote asks to wait longer, the request fails instead of being retried.
+    pub fn max_retry_after(mut self, max: Duration) -> Self {
+        self.max_retry_after = max;
+        self
+    }
+
+    /// Also retry PUT, POST and PATCH requests, which are not idempotent. Disabled by default.
+    pub fn retry_writes(mut self, retry_writes: bool) -> Self {
+        self.retry_writes = retry_writes;
+        self
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Whether a request may be retried at all, based on whether it is idempotent.
+    pub(crate) fn allows(&self, idempotent: bool) -> bool {
+        self.max_attempts > 1 && (idempotent || self.retry_writes)
+    }
+
+    /// The delay to wait after failed attempt number `attempt` (starting at 1),
+    /// or None if the remote asked to wait longer than `max_retry_after`.
+    pub(crate) fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if let (true, Some(retry_after)) = (self.respect_retry_after, retry_after) {
+            return (retry_after <= self.max_retry_after).then_some(retry_after);
+        }
+
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff);
+
+        if self.jitter {
+            let half = backoff / 2;
+            Some(half + half.mul_f64(fastrand::f64()))
+        } else {
+            Some(backoff)
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Whether the error is likely transient and the request worth attempting again.
+/// Being rate limited is handled separately, see `CodesClientBuilder::wait_on_rate_limited`.
+pub(crate) fn is_transient(err: &ClientError) -> bool {
+    !matches!(err, ClientError::RateLimited { .. }) && err.is_retryable()
+}
+
+/// Parses a `Retry-After` header, which is either a number of seconds or an HTTP date.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::error::{ErrorResponse, InnerErrorResponse};
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_none_does_not_retry() {
+        assert!(!RetryPolicy::none().allows(true));
+        assert!(!RetryPolicy::default().allows(true));
+    }
+
+    #[test]
+    fn test_allows_puts_only_when_enabled() {
+        let policy = RetryPolicy::exponential(3);
+        assert!(policy.allows(true));
+        assert!(!policy.allows(false));
+        assert!(policy.retry_writes(true).allows(false));
+    }
+
+    #[test]
+    fn test_delay_doubles_up_to_max() {
+        let policy = RetryPolicy::exponential(10)
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(500))
+            .jitter(false);
+
+        assert_eq!(policy.delay(1, None).unwrap(), Duration::from_millis(100));
+        assert_eq!(policy.delay(2, None), Some(Duration::from_millis(200)));
+        assert_eq!(policy.delay(3, None), Some(Duration::from_millis(400)));
+        assert_eq!(policy.delay(4, None), Some(Duration::from_millis(500)));
+        assert_eq!(policy.delay(40, None), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_delay_with_jitter_stays_in_bounds() {
+        let policy = RetryPolicy::exponential(3).initial_backoff(Duration::from_millis(100));
+
+        for _ in 0..100 {
+            let delay = policy.delay(1, None).unwrap();
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn test_delay_respects_retry_after() {
+        let policy = RetryPolicy::exponential(3);
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(30))),
+            Some(Duration::from_secs(30))
+        );
+
+        let policy = policy.respect_retry_after(false).jitter(false);
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(30))),
+            Some(Duration::from_millis(200))
+        );
+    }
+
+    #[test]
+    fn test_delay_fails_beyond_max_retry_after() {
+        let policy = RetryPolicy::exponential(3);
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(86400))), None);
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(60))),
+            Some(Duration::from_secs(60))
+        );
+
+        let policy = policy.max_retry_after(Duration::from_secs(5));
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(6))), None);
+
+        let policy = policy.respect_retry_after(false);
+        assert!(policy.delay(1, Some(Duration::from_secs(86400))).is_some());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let server_error = |code| {
+            ClientError::ServerError(ErrorResponse {
+                error: InnerErrorResponse {
+                    code,
+                    description: String::new(),
+                    debug: None,
+                },
+            })
+        };
+
+        assert!(is_transient(&server_error(503)));
+        assert!(is_transient(&server_error(500)));
+        assert!(!is_transient(&server_error(404)));
+        assert!(!is_transient(&server_error(401)));
+        assert!(is_transient(&ClientError::UnexpectedResponse {
+            status: reqwest::StatusCode::BAD_GATEWAY,
+            body: String::new(),
+        }));
+        assert!(!is_transient(&ClientError::RateLimited {
+            retry_after: None
+        }));
+    }
+}
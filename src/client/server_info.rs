@@ -0,0 +1,124 @@
+use crate::client::error::ClientError;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// An optional feature of the remote, see `CodesClient::server_info`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Capability {
+    /// Inserting many codes in one request, `PUT /codes/batch`
+    BatchInsert,
+    /// Filtering the list of codes with query parameters
+    Filtering,
+    /// Paginating the list of codes
+    Pagination,
+    /// Merging duplicate sources, `POST /sources/{id}/merge`
+    SourceMerge,
+}
+
+impl Capability {
+    /// The name of the capability in the `features` of the info endpoint.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BatchInsert => "batch_insert",
+            Self::Filtering => "filtering",
+            Self::Pagination => "pagination",
+            Self::SourceMerge => "source_merge",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What a deployment of the remote service told about itself through `GET /info`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// The version of the deployment, e.g. `0.4.0`
+    pub version: Option<String>,
+    /// The version of the API, e.g. 2 if the deployment also serves `/v2`
+    pub api_version: Option<u32>,
+    /// The names of the optional features the deployment supports, see `Capability`
+    pub features: BTreeSet<String>,
+    /// False if the remote has no info endpoint, which deployments predating it lack.
+    /// Nothing is known about the features of such a remote.
+    pub discovered: bool,
+}
+
+impl ServerInfo {
+    /// Whether the remote supports `capability`, or None if the remote did not say.
+    pub fn supports(&self, capability: Capability) -> Option<bool> {
+        match self.discovered {
+            true => Some(self.features.contains(capability.as_str())),
+            false => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RemoteServerInfo {
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    api_version: Option<u32>,
+    #[serde(default)]
+    features: BTreeSet<String>,
+}
+
+impl From<RemoteServerInfo> for ServerInfo {
+    fn from(value: RemoteServerInfo) -> Self {
+        Self {
+            version: value.version,
+            api_version: value.api_version,
+            features: value.features,
+            discovered: true,
+        }
+    }
+}
+
+/// Whether the remote responded that the endpoint of a request does not exist, or does not accept the method.
+pub(crate) fn is_missing_endpoint(err: &ClientError) -> bool {
+    matches!(
+        err.status_code(),
+        Some(reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::METHOD_NOT_ALLOWED)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_supports() {
+        let info: ServerInfo = serde_json::from_str::<RemoteServerInfo>(
+            r#"{"version": "0.4.0", "api_version": 1, "features": ["batch_insert", "shiny"]}"#,
+        )
+        .unwrap()
+        .into();
+
+        assert_eq!(info.version.as_deref(), Some("0.4.0"));
+        assert_eq!(info.supports(Capability::BatchInsert), Some(true));
+        assert_eq!(info.supports(Capability::Filtering), Some(false));
+        assert!(info.features.contains("shiny"));
+
+        assert_eq!(
+            ServerInfo::default().supports(Capability::BatchInsert),
+            None
+        );
+    }
+
+    #[test]
+    fn test_minimal_info() {
+        let info: ServerInfo = serde_json::from_str::<RemoteServerInfo>("{}")
+            .unwrap()
+            .into();
+
+        assert!(info.discovered);
+        assert_eq!(info.api_version, None);
+        assert_eq!(info.supports(Capability::Pagination), Some(false));
+    }
+}
@@ -0,0 +1,181 @@
+#![cfg(feature = "chrono")]
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use std::fmt::{Display, Formatter};
+
+/// Returned when a timestamp of the remote is in none of the known formats.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseTimestampError {
+    value: String,
+}
+
+impl ParseTimestampError {
+    /// The timestamp as the remote sent it.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl Display for ParseTimestampError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown timestamp format: {:?}", self.value)
+    }
+}
+
+impl std::error::Error for ParseTimestampError {}
+
+/// Parses the timestamp formats the remote is known to emit.
+/// Timestamps without a timezone are in UTC.
+pub(crate) fn parse_timestamp(value: &str) -> Result<DateTime<Utc>, ParseTimestampError> {
+    let trimmed = value.trim();
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(trimmed) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(trimmed, format) {
+            return Ok(datetime.and_utc());
+        }
+    }
+
+    let parsed = match NaiveDate::parse_from_str(trimmed, "%Y-%m-%d") {
+        Ok(date) => date.and_hms_opt(0, 0, 0).map(|datetime| datetime.and_utc()),
+        Err(_) => trimmed
+            .parse::<i64>()
+            .ok()
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0)),
+    };
+
+    parsed.ok_or_else(|| ParseTimestampError {
+        value: value.to_string(),
+    })
+}
+
+impl crate::Code {
+    /// `expires_at` parsed into a `DateTime<Utc>`.
+    /// None if the remote did not provide an expiry timestamp, an error if it is in an unknown format.
+    pub fn expires_at_parsed(&self) -> Option<Result<DateTime<Utc>, ParseTimestampError>> {
+        self.expires_at.as_deref().map(parse_timestamp)
+    }
+
+    /// How long the code remains valid at `now`, zero if it already expired.
+    /// None if the remote did not provide an expiry timestamp or it could not be parsed.
+    pub fn time_remaining(&self, now: DateTime<Utc>) -> Option<chrono::Duration> {
+        let expires_at = self.expires_at_parsed()?.ok()?;
+
+        Some((expires_at - now).max(chrono::Duration::zero()))
+    }
+
+    /// Whether the code is expired at `now`, based on its expiry timestamp.
+    /// Falls back to the `expired` flag of the remote if no expiry timestamp is known or it could not be parsed.
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        match self.expires_at_parsed() {
+            Some(Ok(expires_at)) => expires_at <= now,
+            _ => self.expired,
+        }
+    }
+
+    /// Whether the code is still valid, but expires within `duration` from now.
+    /// False if no expiry timestamp is known or it could not be parsed.
+    pub fn expires_within(&self, duration: chrono::Duration) -> bool {
+        let now = Utc::now();
+
+        match self.expires_at_parsed() {
+            Some(Ok(expires_at)) => {
+                expires_at > now
+                    && now
+                        .checked_add_signed(duration)
+                        .is_none_or(|until| expires_at <= until)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Code;
+    use chrono::{Duration, TimeZone};
+
+    fn code(expires_at: Option<DateTime<Utc>>, expired: bool) -> Code {
+        Code {
+            code: "FOOB-BARS-TEST".parse().unwrap(),
+            expired,
+            expires_at: expires_at.map(|expires_at| expires_at.to_rfc3339()),
+            creator: None,
+            submitter: None,
+            lister: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        for value in [
+            "2024-01-01 00:00:00.0",
+            "2024-01-01 00:00:00",
+            "2024-01-01T00:00:00.000",
+            "2024-01-01T00:00:00Z",
+            "2024-01-01T01:00:00+01:00",
+            "2024-01-01",
+            "1704067200",
+        ] {
+            assert_eq!(parse_timestamp(value), Ok(expected), "{}", value);
+        }
+
+        let err = parse_timestamp("next week").unwrap_err();
+        assert_eq!(err.value(), "next week");
+        assert_eq!(err.to_string(), "unknown timestamp format: \"next week\"");
+    }
+
+    #[test]
+    fn test_time_remaining() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            code(Some(now + Duration::hours(2)), false).time_remaining(now),
+            Some(Duration::hours(2))
+        );
+        assert_eq!(
+            code(Some(now - Duration::hours(2)), false).time_remaining(now),
+            Some(Duration::zero())
+        );
+        assert_eq!(code(None, false).time_remaining(now), None);
+    }
+
+    #[test]
+    fn test_is_expired_at() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        assert!(code(Some(now - Duration::seconds(1)), false).is_expired_at(now));
+        assert!(!code(Some(now + Duration::seconds(1)), true).is_expired_at(now));
+        assert!(code(None, true).is_expired_at(now));
+        assert!(!code(None, false).is_expired_at(now));
+    }
+
+    #[test]
+    fn test_unparsable_expiry() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut code = code(None, true);
+        code.expires_at = Some("next week".to_string());
+
+        assert!(matches!(code.expires_at_parsed(), Some(Err(_))));
+        assert_eq!(code.time_remaining(now), None);
+        assert!(code.is_expired_at(now));
+        assert!(!code.expires_within(Duration::MAX));
+    }
+
+    #[test]
+    fn test_expires_within() {
+        let now = Utc::now();
+
+        assert!(code(Some(now + Duration::hours(1)), false).expires_within(Duration::days(1)));
+        assert!(!code(Some(now + Duration::days(2)), false).expires_within(Duration::days(1)));
+        assert!(!code(Some(now - Duration::hours(1)), false).expires_within(Duration::days(1)));
+        assert!(!code(None, false).expires_within(Duration::days(1)));
+        assert!(code(Some(now + Duration::days(2)), false).expires_within(Duration::MAX));
+    }
+}
@@ -0,0 +1,550 @@
+//! Finds candidate codes in free text, such as Discord messages, Reddit posts or wiki pages.
+//!
+//! Codes are found in all the ways people tend to post them: grouped with dashes, spaces or emoji,
+//! lowercase, split across lines or hidden in spoiler tags. Every candidate gets a `Confidence`,
+//! as twelve letter words such as `ANNOUNCEMENT` look exactly like codes.
+//!
+//! ```
+//! use licc::extract::{extract, Confidence};
+//!
+//! let candidates = extract("New code! ||FOOB-BARS-TEST|| more in the announcement");
+//!
+//! assert_eq!(candidates.len(), 2);
+//! assert_eq!(candidates[0].code, "FOOB-BARS-TEST");
+//! assert_eq!(candidates[0].confidence, Confidence::High);
+//! assert_eq!(candidates[1].code, "ANNO-UNCE-MENT");
+//! assert_eq!(candidates[1].confidence, Confidence::Low);
+//! ```
+
+#[cfg(feature = "write")]
+use crate::write::{InsertCodeRequest, SourceLookup};
+use crate::ChestCode;
+
+/// Characters that are removed before scanning, as they are invisible or only used for formatting.
+const INVISIBLE: &[char] = &[
+    '\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}', '\u{AD}',
+];
+
+/// Markdown and spoiler markers that are removed before scanning, as pairs of opening and closing markers.
+/// Some of these contain symbols that may appear in codes, so they are only removed when they wrap text.
+const MARKUP: &[(&str, &str)] = &[
+    ("**", "**"),
+    ("__", "__"),
+    ("~~", "~~"),
+    ("||", "||"),
+    (">!", "!<"),
+    ("`", "`"),
+];
+
+/// Words that announce codes, these are never joined with the code when only separated by whitespace.
+const ANNOUNCEMENT_WORDS: &[&str] = &[
+    "CHEST", "CODE", "CODES", "FREE", "GIFT", "HERE", "NEW", "REDEEM", "USE",
+];
+
+/// HTML elements that do not separate their content from the surrounding text.
+const INLINE_ELEMENTS: &[&str] = &[
+    "a", "b", "code", "em", "i", "mark", "s", "small", "span", "strong", "sub", "sup", "u",
+];
+
+/// How likely it is that a candidate is an actual code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Confidence {
+    /// Likely a regular word, e.g. `announcement`.
+    Low,
+    /// Could be a code, e.g. `FOOBBARSTEST`.
+    Medium,
+    /// Almost certainly a code, e.g. `FOOB-BARS-TEST` or `L0V3-Y0UR-D1C3`.
+    High,
+}
+
+/// A code found in text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    pub code: ChestCode,
+    pub confidence: Confidence,
+    /// The text the code was found in, as it appeared after removing markup.
+    pub raw: String,
+}
+
+/// Finds codes in plain text or Markdown, ordered by first appearance.
+/// Codes that appear more than once are returned once, with the highest confidence they were found with.
+pub fn extract(text: &str) -> Vec<Candidate> {
+    let text: String = text.chars().filter(|c| !INVISIBLE.contains(c)).collect();
+    let text = strip_markup(text);
+
+    let mut candidates: Vec<Candidate> = Vec::new();
+
+    for candidate in scan(&tokenize(&text)) {
+        match candidates.iter_mut().find(|c| c.code == candidate.code) {
+            Some(existing) if existing.confidence < candidate.confidence => *existing = candidate,
+            Some(_) => {}
+            None => candidates.push(candidate),
+        }
+    }
+
+    candidates
+}
+
+/// Finds codes in HTML, see `extract`.
+pub fn extract_html(html: &str) -> Vec<Candidate> {
+    extract(&decode_entities(&strip_tags(html)))
+}
+
+/// Turns the candidates with at least `min_confidence` into requests to insert them,
+/// attributed to `creator`.
+#[cfg(feature = "write")]
+pub fn insert_requests(
+    candidates: impl IntoIterator<Item = Candidate>,
+    min_confidence: Confidence,
+    creator: SourceLookup,
+    expires_at: u64,
+) -> Vec<InsertCodeRequest> {
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.confidence >= min_confidence)
+        .map(|candidate| InsertCodeRequest {
+            code: candidate.code,
+            expires_at,
+            creator: creator.clone(),
+            submitter: None,
+        })
+        .collect()
+}
+
+/// What separates a token from the previous one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Separator {
+    /// Only dashes
+    Dash,
+    /// Whitespace, at most one line break, emoji or other non-ASCII symbols, mixed with dashes
+    Loose,
+    /// Whitespace, at most one line break, emoji or other non-ASCII symbols, without dashes
+    Space,
+    /// Anything else, tokens are not part of the same code
+    Break,
+}
+
+/// A run of characters that may appear in codes.
+#[derive(Debug)]
+struct Token {
+    text: String,
+    separator: Separator,
+}
+
+fn is_code_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!@#$%^&*".contains(c)
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut separator = String::new();
+
+    let classify = |separator: &str| {
+        if separator.is_empty() || separator.chars().all(|c| c == '-') {
+            return Separator::Dash;
+        }
+        let loose = separator
+            .chars()
+            .all(|c| c == '-' || c.is_whitespace() || (!c.is_ascii() && !c.is_alphanumeric()));
+        if !loose || separator.matches('\n').count() > 1 {
+            Separator::Break
+        } else if separator.contains('-') {
+            Separator::Loose
+        } else {
+            Separator::Space
+        }
+    };
+
+    for c in text.chars() {
+        if is_code_char(c) {
+            current.push(c);
+            continue;
+        }
+        if !current.is_empty() {
+            tokens.push(Token {
+                text: std::mem::take(&mut current),
+                separator: match tokens.is_empty() {
+                    true => Separator::Break,
+                    false => classify(&separator),
+                },
+            });
+            separator.clear();
+        }
+        separator.push(c);
+    }
+
+    if !current.is_empty() {
+        tokens.push(Token {
+            text: current,
+            separator: match tokens.is_empty() {
+                true => Separator::Break,
+                false => classify(&separator),
+            },
+        });
+    }
+
+    tokens
+}
+
+/// Whether the token has no lowercase letters.
+fn is_upper(token: &str) -> bool {
+    !token.chars().any(|c| c.is_ascii_lowercase())
+}
+
+/// Joins consecutive tokens into codes, preferring the longest code at every position.
+fn scan(tokens: &[Token]) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    let mut start = 0;
+
+    while start < tokens.len() {
+        match longest_code(&tokens[start..]) {
+            Some((len, candidate)) => {
+                candidates.push(candidate);
+                start += len;
+            }
+            None => start += 1,
+        }
+    }
+
+    candidates
+}
+
+/// The longest code made of the first tokens, and how many tokens it spans.
+fn longest_code(tokens: &[Token]) -> Option<(usize, Candidate)> {
+    let mut found = None;
+    let mut length = 0;
+
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 {
+            // Codes split over several tokens are grouped in blocks of four,
+            // and consistently upper- or lowercase to avoid joining regular words with codes.
+            let grouped = tokens[..=i].iter().all(|t| t.text.len() % 4 == 0);
+            let consistent = tokens[..=i]
+                .iter()
+                .all(|t| is_upper(&t.text) == is_upper(&tokens[0].text));
+            // A code grouped with dashes is not extended by a word next to it, and vice versa.
+            let spaced = |t: &Token| t.separator == Separator::Space;
+            let uniform = tokens[1..=i].iter().all(|t| spaced(t) == spaced(token));
+            let announcement = spaced(token)
+                && tokens[..=i].iter().any(|t| {
+                    ANNOUNCEMENT_WORDS
+                        .iter()
+                        .any(|word| t.text.eq_ignore_ascii_case(word))
+                });
+
+            if token.separator == Separator::Break
+                || !grouped
+                || !consistent
+                || !uniform
+                || announcement
+            {
+                break;
+            }
+        }
+
+        length += token.text.len();
+        if length > 16 {
+            break;
+        }
+        if length == 12 || length == 16 {
+            let raw = tokens[..=i]
+                .iter()
+                .map(|t| t.text.as_str())
+                .collect::<Vec<_>>()
+                .join("-");
+            if let Ok(code) = raw.parse::<ChestCode>() {
+                let confidence = confidence(&tokens[..=i]);
+                found = Some((
+                    i + 1,
+                    Candidate {
+                        code,
+                        confidence,
+                        raw,
+                    },
+                ));
+            }
+        }
+    }
+
+    found
+}
+
+fn confidence(tokens: &[Token]) -> Confidence {
+    let mut points = 0;
+
+    if tokens.iter().all(|t| is_upper(&t.text)) {
+        points += 2;
+    }
+    if tokens
+        .iter()
+        .any(|t| t.text.chars().any(|c| !c.is_ascii_alphabetic()))
+    {
+        points += 1;
+    }
+    if tokens.len() > 1 && tokens[1..].iter().all(|t| t.separator == Separator::Dash) {
+        points += 1;
+    }
+
+    match points {
+        3.. => Confidence::High,
+        2 => Confidence::Medium,
+        _ => Confidence::Low,
+    }
+}
+
+/// Blanks out pairs of `MARKUP` that wrap text, and leaves markers that are part of a code alone.
+/// A marker opens when it follows a character that cannot appear in codes and precedes text,
+/// and closes when it follows text and precedes a character that cannot appear in codes.
+fn strip_markup(mut text: String) -> String {
+    for (open, close) in MARKUP {
+        let mut from = 0;
+
+        while let Some(start) = find_marker(&text, open, from, true) {
+            let inner = start + open.len();
+            let Some(end) = find_marker(&text, close, inner, false) else {
+                break;
+            };
+
+            // Markers are replaced by spaces of the same length to keep the positions of the text after them.
+            text.replace_range(start..inner, &" ".repeat(open.len()));
+            text.replace_range(end..end + close.len(), &" ".repeat(close.len()));
+            from = end + close.len();
+        }
+    }
+
+    text
+}
+
+/// The position of the first opening or closing `marker` at or after `from`, see `strip_markup`.
+fn find_marker(text: &str, marker: &str, from: usize, opening: bool) -> Option<usize> {
+    text[from..]
+        .match_indices(marker)
+        .map(|(i, _)| from + i)
+        .find(|&i| {
+            let before = text[..i].chars().next_back();
+            let after = text[i + marker.len()..].chars().next();
+            let (outside, inside) = match opening {
+                true => (before, after),
+                false => (after, before),
+            };
+
+            outside.is_none_or(|c| !is_code_char(c)) && inside.is_some_and(|c| !c.is_whitespace())
+        })
+}
+
+/// Removes HTML tags, block elements are replaced by a line break.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(open) = rest.find('<') {
+        text.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('>') else {
+            rest = &rest[open..];
+            break;
+        };
+
+        let tag = &rest[open + 1..open + close];
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if !INLINE_ELEMENTS.contains(&name.as_str()) {
+            text.push('\n');
+        }
+
+        rest = &rest[open + close + 1..];
+    }
+
+    text.push_str(rest);
+    text
+}
+
+/// Decodes the HTML entities that are likely to appear in or around codes.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| Some((end, decode_entity(&rest[1..end])?)));
+
+        match entity {
+            Some((end, c)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let number = entity.strip_prefix('#')?;
+            let number = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(number)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn codes(candidates: &[Candidate]) -> Vec<&str> {
+        candidates.iter().map(|c| c.code.as_str()).collect()
+    }
+
+    #[test]
+    fn test_extract_formats() {
+        for text in [
+            "FOOB-BARS-TEST",
+            "Code: FOOBBARSTEST",
+            "use code FOOB BARS TEST now",
+            "foob-bars-test",
+            "FOOB\u{200B}-BARS-\u{200D}TEST",
+            "FOOB🔥BARS🔥TEST",
+            "FOOB-BARS-\nTEST",
+            "||FOOB-BARS-TEST||",
+            ">!FOOB-BARS-TEST!<",
+            "**FOOB-BARS-TEST**",
+            "`FOOB-BARS-TEST`",
+        ] {
+            assert_eq!(codes(&extract(text)), vec!["FOOB-BARS-TEST"], "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_extract_long_codes_and_symbols() {
+        assert_eq!(
+            codes(&extract("L0V3-Y0UR-D1C3-!#$* and ABCD-1234-EFGH")),
+            vec!["L0V3-Y0UR-D1C3-!#$*", "ABCD-1234-EFGH"]
+        );
+    }
+
+    #[test]
+    fn test_extract_keeps_symbols_that_look_like_markup() {
+        assert_eq!(
+            codes(&extract("L0V3-Y0UR-D1C3-!#**")),
+            vec!["L0V3-Y0UR-D1C3-!#**"]
+        );
+        assert_eq!(
+            codes(&extract("**L0V3-Y0UR-D1C3-!#****")),
+            vec!["L0V3-Y0UR-D1C3-!#**"]
+        );
+        assert_eq!(
+            codes(&extract("**New code:** `FOOB-BARS-TEST`")),
+            vec!["FOOB-BARS-TEST"]
+        );
+        assert_eq!(
+            extract("L0V3-Y0UR-D1C3-!#**")[0].confidence,
+            Confidence::High
+        );
+    }
+
+    #[test]
+    fn test_extract_announcements() {
+        for text in [
+            "NEW CODE FOOB-BARS-TEST",
+            "NEW CODE: FOOB-BARS-TEST",
+            "USE CODE FOOB BARS TEST NOW",
+            "FREE CHEST CODE FOOB BARS TEST",
+            "CODE FOOB-BARS-TEST LIVE",
+            "new code foob-bars-test",
+        ] {
+            assert_eq!(codes(&extract(text)), vec!["FOOB-BARS-TEST"], "{}", text);
+        }
+
+        assert_eq!(
+            codes(&extract("NEW CODE L0V3-Y0UR-D1C3-!#$* LIVE")),
+            vec!["L0V3-Y0UR-D1C3-!#$*"]
+        );
+    }
+
+    #[test]
+    fn test_extract_ignores_non_codes() {
+        assert!(extract("FOOB-BARS-TESTS, FOOB-BARS, FOOB\n\nBARS\n\nTEST").is_empty());
+        assert!(extract("FOOB.BARS.TEST").is_empty());
+    }
+
+    #[test]
+    fn test_confidence() {
+        let candidates = extract("FOOB-BARS-TEST FOOBBARSTES2 announcement L0V3Y0URD1C3");
+
+        assert_eq!(
+            candidates
+                .iter()
+                .map(|c| (c.code.as_str(), c.confidence))
+                .collect::<Vec<_>>(),
+            vec![
+                ("FOOB-BARS-TEST", Confidence::High),
+                ("FOOB-BARS-TES2", Confidence::High),
+                ("ANNO-UNCE-MENT", Confidence::Low),
+                ("L0V3-Y0UR-D1C3", Confidence::High),
+            ]
+        );
+        assert_eq!(extract("FOOBBARSTEST")[0].confidence, Confidence::Medium);
+    }
+
+    #[test]
+    fn test_extract_dedupes() {
+        let candidates = extract("foob-bars-test, or FOOB-BARS-TEST");
+
+        assert_eq!(codes(&candidates), vec!["FOOB-BARS-TEST"]);
+        assert_eq!(candidates[0].confidence, Confidence::High);
+    }
+
+    #[test]
+    fn test_extract_html() {
+        let html = "<p>New code: <b>FOOB</b>-BARS-TEST</p><p>L0V3-Y0UR-D1C3-&#33;&#x23;&amp;*</p>";
+
+        assert_eq!(
+            codes(&extract_html(html)),
+            vec!["FOOB-BARS-TEST", "L0V3-Y0UR-D1C3-!#&*"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn test_insert_requests() {
+        let creator = SourceLookup {
+            name: "Example Creator".to_string(),
+            url: "https://creator.example.org".to_string(),
+        };
+        let requests = insert_requests(
+            extract("FOOB-BARS-TEST announcement"),
+            Confidence::Medium,
+            creator,
+            crate::write::expires_in(std::time::Duration::from_secs(604800)),
+        );
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].code, "FOOB-BARS-TEST");
+        assert_eq!(requests[0].creator.name, "Example Creator");
+    }
+}
@@ -1,19 +1,36 @@
+pub mod api;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod client;
+pub mod extract;
+pub mod seen;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod watch;
 #[cfg(feature = "write")]
 pub mod write;
 
 pub mod api_key;
+mod chest_code;
+mod expiry;
+mod source_index;
+
+pub use chest_code::{ChestCode, ParseChestCodeError};
+#[cfg(feature = "chrono")]
+pub use expiry::ParseTimestampError;
+pub use source_index::SourceIndex;
 
 /// Code represents a code that can be redeemed in Idle Champions of the Forgotten Realms.
 /// For more information, visit https://idlechampions.fandom.com/wiki/Combinations
 #[derive(Debug, Clone)]
 pub struct Code {
     /// The code itself that can be redeemed in-game.
-    pub code: String,
+    pub code: ChestCode,
     /// Whether the code has likely expired, based on the expires_at timestamp.
     /// This information is often not incredibly accurate and the code may still work.
     pub expired: bool,
-    /// A string RFC3339 timestamp of when the code expires.
+    /// When the code expires, exactly as the remote sent it, e.g. `2024-01-01 00:00:00.0`.
+    /// With the `chrono` feature, `expires_at_parsed` parses it into a `DateTime<Utc>`.
     /// This information is often not incredibly accurate and the code may still work.
     pub expires_at: Option<String>,
     /// The creator is the person who "created" the code. This is usually a streamer or developer.
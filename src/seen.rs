@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A code that has been processed before, as stored in a `SeenStore`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SeenEntry {
+    /// The code itself that can be redeemed in-game.
+    pub code: String,
+    /// A unix timestamp of when the code was first recorded.
+    pub first_seen: u64,
+    /// Whether the code has been redeemed.
+    pub redeemed: bool,
+    /// Whether the code has been announced, e.g. posted to a channel.
+    pub announced: bool,
+}
+
+/// Any error that can happen while reading or writing a `SeenStore`
+#[derive(Debug)]
+pub enum StoreError {
+    /// The store could not be read or written
+    Io(std::io::Error),
+    /// An entry failed to serialize or deserialize
+    Serde(serde_json::Error),
+    /// The SQLite database returned an error
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+}
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(_) => f.write_str("failed to read or write the store"),
+            Self::Serde(_) => f.write_str("failed to (de)serialize an entry of the store"),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(_) => f.write_str("the SQLite database returned an error"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Serde(err) => Some(err),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(err) => Some(err),
+        }
+    }
+}
+
+/// SeenStore records which codes have already been processed,
+/// so that a restarted application does not process them again.
+///
+/// Implementations must be usable from multiple tasks at once.
+pub trait SeenStore: Send + Sync {
+    /// Record that `code` has been seen. Returns true if it had not been seen before.
+    fn record(&self, code: &str) -> Result<bool, StoreError>;
+
+    /// The entry of `code`, if it has been seen.
+    fn get(&self, code: &str) -> Result<Option<SeenEntry>, StoreError>;
+
+    /// Mark `code` as redeemed, recording it first if it had not been seen before.
+    fn mark_redeemed(&self, code: &str) -> Result<(), StoreError>;
+
+    /// Mark `code` as announced, recording it first if it had not been seen before.
+    fn mark_announced(&self, code: &str) -> Result<(), StoreError>;
+
+    /// Remove all entries first seen longer than `retention` ago. Returns the number of removed entries.
+    fn prune(&self, retention: Duration) -> Result<usize, StoreError>;
+
+    /// All entries in the store, in no particular order.
+    fn entries(&self) -> Result<Vec<SeenEntry>, StoreError>;
+
+    fn contains(&self, code: &str) -> Result<bool, StoreError> {
+        Ok(self.get(code)?.is_some())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn new_entry(code: &str) -> SeenEntry {
+    SeenEntry {
+        code: code.to_string(),
+        first_seen: unix_now(),
+        redeemed: false,
+        announced: false,
+    }
+}
+
+/// A `SeenStore` backed by a file with one JSON entry per line.
+///
+/// All entries are kept in memory, every change appends a line with the updated entry to the file.
+/// Later lines take precedence over earlier lines of the same code, `prune` rewrites the file without duplicates.
+/// A malformed last line, as left behind by a crash while appending, is cut off when opening the store.
+#[derive(Debug)]
+pub struct JsonLinesStore {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, SeenEntry>>,
+}
+
+impl JsonLinesStore {
+    /// Open the store at `path`, creating the file if it does not exist.
+    /// Fails if a line other than the last one is malformed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(StoreError::Io)?;
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).map_err(StoreError::Io)?;
+
+        let mut entries = HashMap::new();
+        let mut offset = 0;
+        let mut lines = contents.split_inclusive(|b| *b == b'\n').peekable();
+        while let Some(line) = lines.next() {
+            let start = offset;
+            offset += line.len();
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            match serde_json::from_slice::<SeenEntry>(line) {
+                Ok(entry) => {
+                    entries.insert(entry.code.clone(), entry);
+                }
+                Err(_) if lines.peek().is_none() => {
+                    file.set_len(start as u64).map_err(StoreError::Io)?;
+                    offset = start;
+                }
+                Err(err) => return Err(StoreError::Serde(err)),
+            }
+        }
+
+        // A complete entry without its line break would be joined with the next appended line.
+        if offset > 0 && contents[offset - 1] != b'\n' {
+            file.write_all(b"\n").map_err(StoreError::Io)?;
+        }
+
+        Ok(Self {
+            path,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    fn entries_lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, SeenEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn append(&self, entry: &SeenEntry) -> Result<(), StoreError> {
+        let mut line = serde_json::to_string(entry).map_err(StoreError::Serde)?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(StoreError::Io)
+    }
+
+    fn update(&self, code: &str, f: impl FnOnce(&mut SeenEntry)) -> Result<(), StoreError> {
+        let mut entries = self.entries_lock();
+        let mut entry = entries
+            .get(code)
+            .cloned()
+            .unwrap_or_else(|| new_entry(code));
+        f(&mut entry);
+
+        self.append(&entry)?;
+        entries.insert(code.to_string(), entry);
+
+        Ok(())
+    }
+}
+
+impl SeenStore for JsonLinesStore {
+    fn record(&self, code: &str) -> Result<bool, StoreError> {
+        let mut entries = self.entries_lock();
+        if entries.contains_key(code) {
+            return Ok(false);
+        }
+
+        let entry = new_entry(code);
+        self.append(&entry)?;
+        entries.insert(code.to_string(), entry);
+
+        Ok(true)
+    }
+
+    fn get(&self, code: &str) -> Result<Option<SeenEntry>, StoreError> {
+        Ok(self.entries_lock().get(code).cloned())
+    }
+
+    fn mark_redeemed(&self, code: &str) -> Result<(), StoreError> {
+        self.update(code, |entry| entry.redeemed = true)
+    }
+
+    fn mark_announced(&self, code: &str) -> Result<(), StoreError> {
+        self.update(code, |entry| entry.announced = true)
+    }
+
+    fn prune(&self, retention: Duration) -> Result<usize, StoreError> {
+        let cutoff = unix_now().saturating_sub(retention.as_secs());
+        let mut entries = self.entries_lock();
+        let pruned: HashMap<String, SeenEntry> = entries
+            .iter()
+            .filter(|(_, entry)| entry.first_seen >= cutoff)
+            .map(|(code, entry)| (code.clone(), entry.clone()))
+            .collect();
+
+        // Rewrite into a temporary file first, so a failure halfway never loses the existing store.
+        // The entries in memory are only replaced once the file is, so both stay the same on failure.
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp).map_err(StoreError::Io)?;
+        for entry in pruned.values() {
+            let line = serde_json::to_string(entry).map_err(StoreError::Serde)?;
+            writeln!(file, "{}", line).map_err(StoreError::Io)?;
+        }
+        file.sync_all().map_err(StoreError::Io)?;
+        std::fs::rename(&tmp, &self.path).map_err(StoreError::Io)?;
+
+        let removed = entries.len() - pruned.len();
+        *entries = pruned;
+
+        Ok(removed)
+    }
+
+    fn entries(&self) -> Result<Vec<SeenEntry>, StoreError> {
+        Ok(self.entries_lock().values().cloned().collect())
+    }
+}
+
+/// A `SeenStore` backed by a SQLite database. Requires the `sqlite` feature.
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    /// Open the database at `path`, creating it and the `seen_codes` table if they do not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::from_connection(rusqlite::Connection::open(path).map_err(StoreError::Sqlite)?)
+    }
+
+    /// A store that lives in memory only, mostly useful for testing.
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::from_connection(rusqlite::Connection::open_in_memory().map_err(StoreError::Sqlite)?)
+    }
+
+    fn from_connection(connection: rusqlite::Connection) -> Result<Self, StoreError> {
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS seen_codes (
+                    code TEXT PRIMARY KEY NOT NULL,
+                    first_seen INTEGER NOT NULL,
+                    redeemed INTEGER NOT NULL DEFAULT 0,
+                    announced INTEGER NOT NULL DEFAULT 0
+                )",
+            )
+            .map_err(StoreError::Sqlite)?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, rusqlite::Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn insert_ignore(connection: &rusqlite::Connection, code: &str) -> Result<usize, StoreError> {
+        connection
+            .execute(
+                "INSERT OR IGNORE INTO seen_codes (code, first_seen) VALUES (?1, ?2)",
+                rusqlite::params![code, unix_now() as i64],
+            )
+            .map_err(StoreError::Sqlite)
+    }
+
+    fn entry(row: &rusqlite::Row) -> rusqlite::Result<SeenEntry> {
+        Ok(SeenEntry {
+            code: row.get(0)?,
+            first_seen: row.get::<_, i64>(1)? as u64,
+            redeemed: row.get(2)?,
+            announced: row.get(3)?,
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl SeenStore for SqliteStore {
+    fn record(&self, code: &str) -> Result<bool, StoreError> {
+        Ok(Self::insert_ignore(&self.connection(), code)? > 0)
+    }
+
+    fn get(&self, code: &str) -> Result<Option<SeenEntry>, StoreError> {
+        use rusqlite::OptionalExtension;
+
+        self.connection()
+            .query_row(
+                "SELECT code, first_seen, redeemed, announced FROM seen_codes WHERE code = ?1",
+                [code],
+                Self::entry,
+            )
+            .optional()
+            .map_err(StoreError::Sqlite)
+    }
+
+    fn mark_redeemed(&self, code: &str) -> Result<(), StoreError> {
+        let connection = self.connection();
+        Self::insert_ignore(&connection, code)?;
+        connection
+            .execute("UPDATE seen_codes SET redeemed = 1 WHERE code = ?1", [code])
+            .map_err(StoreError::Sqlite)?;
+
+        Ok(())
+    }
+
+    fn mark_announced(&self, code: &str) -> Result<(), StoreError> {
+        let connection = self.connection();
+        Self::insert_ignore(&connection, code)?;
+        connection
+            .execute(
+                "UPDATE seen_codes SET announced = 1 WHERE code = ?1",
+                [code],
+            )
+            .map_err(StoreError::Sqlite)?;
+
+        Ok(())
+    }
+
+    fn prune(&self, retention: Duration) -> Result<usize, StoreError> {
+        let cutoff = unix_now().saturating_sub(retention.as_secs());
+
+        self.connection()
+            .execute(
+                "DELETE FROM seen_codes WHERE first_seen < ?1",
+                [cutoff as i64],
+            )
+            .map_err(StoreError::Sqlite)
+    }
+
+    fn entries(&self) -> Result<Vec<SeenEntry>, StoreError> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare("SELECT code, first_seen, redeemed, announced FROM seen_codes")
+            .map_err(StoreError::Sqlite)?;
+        let entries = statement
+            .query_map([], Self::entry)
+            .map_err(StoreError::Sqlite)?
+            .collect::<rusqlite::Result<Vec<SeenEntry>>>()
+            .map_err(StoreError::Sqlite)?;
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("licc-test-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn exercise(store: &dyn SeenStore) {
+        assert!(!store.contains("AAAA-BBBB-CCCC").unwrap());
+        assert!(store.record("AAAA-BBBB-CCCC").unwrap());
+        assert!(!store.record("AAAA-BBBB-CCCC").unwrap());
+        assert!(store.contains("AAAA-BBBB-CCCC").unwrap());
+
+        store.mark_announced("AAAA-BBBB-CCCC").unwrap();
+        store.mark_redeemed("DDDD-EEEE-FFFF").unwrap();
+
+        let entry = store.get("AAAA-BBBB-CCCC").unwrap().unwrap();
+        assert!(entry.announced);
+        assert!(!entry.redeemed);
+
+        let entry = store.get("DDDD-EEEE-FFFF").unwrap().unwrap();
+        assert!(!entry.announced);
+        assert!(entry.redeemed);
+
+        assert_eq!(store.entries().unwrap().len(), 2);
+        assert_eq!(store.prune(Duration::from_secs(3600)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_json_lines_store() {
+        let path = test_path("seen");
+        exercise(&JsonLinesStore::open(&path).unwrap());
+
+        // Reopening restores the latest state of every entry.
+        let store = JsonLinesStore::open(&path).unwrap();
+        assert_eq!(store.entries().unwrap().len(), 2);
+        assert!(store.get("AAAA-BBBB-CCCC").unwrap().unwrap().announced);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_json_lines_store_partial_last_line() {
+        let path = test_path("partial");
+        let entry =
+            "{\"code\":\"AAAA-BBBB-CCCC\",\"first_seen\":0,\"redeemed\":false,\"announced\":false}";
+        std::fs::write(&path, format!("{}\n{{\"code\":\"DDDD-EE", entry)).unwrap();
+
+        let store = JsonLinesStore::open(&path).unwrap();
+        assert!(store.contains("AAAA-BBBB-CCCC").unwrap());
+        assert_eq!(store.entries().unwrap().len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{}\n", entry)
+        );
+
+        store.record("DDDD-EEEE-FFFF").unwrap();
+        let store = JsonLinesStore::open(&path).unwrap();
+        assert_eq!(store.entries().unwrap().len(), 2);
+
+        // A complete last entry that lost its line break is kept.
+        std::fs::write(&path, entry).unwrap();
+        JsonLinesStore::open(&path)
+            .unwrap()
+            .record("DDDD-EEEE-FFFF")
+            .unwrap();
+        assert_eq!(
+            JsonLinesStore::open(&path)
+                .unwrap()
+                .entries()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_json_lines_store_corrupt_line() {
+        let path = test_path("corrupt");
+        let entry =
+            "{\"code\":\"AAAA-BBBB-CCCC\",\"first_seen\":0,\"redeemed\":false,\"announced\":false}";
+        std::fs::write(&path, format!("{{\"code\":\"DDDD-EE\n{}\n", entry)).unwrap();
+
+        assert!(matches!(
+            JsonLinesStore::open(&path),
+            Err(StoreError::Serde(_))
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_store_error() {
+        let err: Box<dyn std::error::Error> =
+            Box::new(JsonLinesStore::open(std::env::temp_dir()).unwrap_err());
+
+        assert_eq!(err.to_string(), "failed to read or write the store");
+        assert!(err.source().unwrap().is::<std::io::Error>());
+    }
+
+    #[test]
+    fn test_json_lines_store_prune() {
+        let path = test_path("prune");
+        std::fs::write(
+            &path,
+            "{\"code\":\"AAAA-BBBB-CCCC\",\"first_seen\":0,\"redeemed\":false,\"announced\":false}\n",
+        )
+        .unwrap();
+
+        let store = JsonLinesStore::open(&path).unwrap();
+        store.record("DDDD-EEEE-FFFF").unwrap();
+        assert_eq!(store.prune(Duration::from_secs(3600)).unwrap(), 1);
+
+        let store = JsonLinesStore::open(&path).unwrap();
+        assert!(!store.contains("AAAA-BBBB-CCCC").unwrap());
+        assert!(store.contains("DDDD-EEEE-FFFF").unwrap());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_json_lines_store_failed_prune() {
+        let path = test_path("failed-prune");
+        std::fs::write(
+            &path,
+            "{\"code\":\"AAAA-BBBB-CCCC\",\"first_seen\":0,\"redeemed\":false,\"announced\":false}\n",
+        )
+        .unwrap();
+        let store = JsonLinesStore::open(&path).unwrap();
+
+        // The temporary file cannot be created where a directory is in the way.
+        let tmp = path.with_extension("tmp");
+        std::fs::create_dir_all(&tmp).unwrap();
+
+        assert!(store.prune(Duration::from_secs(3600)).is_err());
+        assert!(store.contains("AAAA-BBBB-CCCC").unwrap());
+
+        std::fs::remove_dir(tmp).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_sqlite_store() {
+        exercise(&SqliteStore::open_in_memory().unwrap());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::Source;
+use std::collections::{BTreeMap, HashMap};
+
+/// SourceIndex is the deduplicated table of sources the remote knows about, by ID.
+///
+/// Codes refer to their creator, submitter and lister by ID, the index resolves those
+/// and allows curating attribution without going through every code.
+#[derive(Clone, Debug, Default)]
+pub struct SourceIndex(BTreeMap<i32, Source>);
+
+impl SourceIndex {
+    /// The source with the given ID, if it is known.
+    pub fn get(&self, id: i32) -> Option<&Source> {
+        self.0.get(&id)
+    }
+
+    /// All sources with the given name, ignoring case.
+    /// Sources are unique (name, url) pairs, so one name may have several sources.
+    pub fn find_by_name<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Source> {
+        self.0
+            .values()
+            .filter(move |source| source.name.eq_ignore_ascii_case(name))
+    }
+
+    /// All sources, ordered by ID.
+    pub fn iter(&self) -> impl Iterator<Item = &Source> {
+        self.0.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<HashMap<i32, Source>> for SourceIndex {
+    fn from(sources: HashMap<i32, Source>) -> Self {
+        Self(sources.into_iter().collect())
+    }
+}
+
+impl FromIterator<Source> for SourceIndex {
+    fn from_iter<I: IntoIterator<Item = Source>>(iter: I) -> Self {
+        Self(iter.into_iter().map(|source| (source.id, source)).collect())
+    }
+}
+
+impl IntoIterator for SourceIndex {
+    type Item = Source;
+    type IntoIter = std::collections::btree_map::IntoValues<i32, Source>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_values()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn source(id: i32, name: &str) -> Source {
+        Source {
+            id,
+            name: name.to_string(),
+            url: format!("https://{}.example", id),
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        let index: SourceIndex = [source(2, "Foo"), source(1, "foo"), source(3, "bar")]
+            .into_iter()
+            .collect();
+
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.get(3).unwrap().name, "bar");
+        assert!(index.get(4).is_none());
+        assert_eq!(
+            index.find_by_name("FOO").map(|s| s.id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            index.iter().map(|s| s.id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+    }
+}
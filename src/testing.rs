@@ -0,0 +1,655 @@
+//! An in-process fake of the codes API, for tests that cannot reach the remote.
+//!
+//! The fake serves seeded codes and sources on a random local port, checks the credentials of write requests
+//! and can be told to fail requests the ways the remote does. Requires the `testing` feature.
+//!
+//! ```
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! use licc::client::CodesClient;
+//! use licc::testing::{Fault, FakeServer};
+//!
+//! let server = FakeServer::start().await;
+//! server.add_code(FakeServer::code("FOOB-BARS-TEST"));
+//!
+//! let client = CodesClient::new_full(None, Some(server.base_url()), None);
+//! assert_eq!(client.get_codes().await.unwrap()[0].code, "FOOB-BARS-TEST");
+//!
+//! server.fail_next(Fault::NotFound);
+//! assert!(client.get_codes().await.unwrap_err().is_not_found());
+//! # }
+//! ```
+#![cfg(feature = "testing")]
+
+use crate::{ChestCode, Code, Source};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A way to fail a request, mimicking the errors of the remote.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// 404 Not Found
+    NotFound,
+    /// 401 Unauthorized, as if the API key was invalid
+    Unauthorized,
+    /// 422 Unprocessable Entity, in the format of the framework the remote is built with
+    Unprocessable,
+    /// 500 Internal Server Error
+    InternalServerError,
+    /// 502 Bad Gateway with an HTML body, as a proxy in front of the remote would respond
+    BadGateway,
+    /// 429 Too Many Requests, with the given `Retry-After` in seconds
+    RateLimited(Option<u64>),
+    /// Respond as usual, but only after waiting this long
+    Slow(Duration),
+    /// 200 OK with a body that is not valid JSON
+    MalformedJson,
+}
+
+/// A request the fake received.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// The path and query, without the `/v1` prefix
+    pub path: String,
+    pub body: String,
+}
+
+#[derive(Default)]
+struct State {
+    codes: Vec<(i32, Code)>,
+    sources: BTreeMap<i32, Source>,
+    api_key: Option<String>,
+    next_id: i32,
+    next_faults: VecDeque<Fault>,
+    fault: Option<Fault>,
+    requests: Vec<RecordedRequest>,
+    features: Option<Vec<String>>,
+}
+
+struct Response {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: impl ToString) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.to_string(),
+        }
+    }
+
+    /// An error in the format of the remote
+    fn error(status: u16, description: &str) -> Self {
+        Self::json(
+            status,
+            serde_json::json!({
+                "error": { "code": status, "description": description, "debug": null }
+            }),
+        )
+    }
+}
+
+/// FakeServer is an in-process HTTP server implementing the codes API.
+/// It stops when dropped.
+pub struct FakeServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl FakeServer {
+    /// Starts the fake on a random port of localhost, without any codes or sources.
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("should be able to listen on localhost");
+        let addr = listener
+            .local_addr()
+            .expect("listener should have an address");
+        let state = Arc::new(Mutex::new(State {
+            next_id: 1,
+            ..State::default()
+        }));
+
+        let task = tokio::spawn(serve(listener, state.clone()));
+
+        Self { addr, state, task }
+    }
+
+    /// The base URL to point a client at, e.g. `http://127.0.0.1:1234/v1`.
+    pub fn base_url(&self) -> String {
+        format!("http://{}/v1", self.addr)
+    }
+
+    /// A code that has not expired, without an expiry timestamp or sources, for seeding.
+    /// Panics if `code` is not a valid code.
+    pub fn code(code: &str) -> Code {
+        Code {
+            code: code.parse().expect("seeded code should be valid"),
+            expired: false,
+            expires_at: None,
+            creator: None,
+            submitter: None,
+            lister: None,
+        }
+    }
+
+    /// Seeds a code, its sources are seeded as well.
+    pub fn add_code(&self, code: Code) {
+        let mut state = self.state();
+
+        for source in [&code.creator, &code.submitter, &code.lister]
+            .into_iter()
+            .flatten()
+        {
+            state.sources.insert(source.id, source.clone());
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.codes.push((id, code));
+    }
+
+    /// Seeds a source that no code refers to.
+    pub fn add_source(&self, source: Source) {
+        self.state().sources.insert(source.id, source);
+    }
+
+    /// Requires write requests to send this API key, any key is accepted until this is set.
+    ///
+    /// The key is accepted in the `X-Api-Key` header, as an `Authorization: Bearer` token,
+    /// or, with the `signing` feature, as the key of the signature of a `client::HmacSigner`.
+    pub fn api_key(&self, api_key: &str) {
+        self.state().api_key = Some(api_key.to_string());
+    }
+
+    /// Serves `GET /info` listing these features, see `client::Capability`.
+    /// Until this is called the fake has no info endpoint, like deployments predating it.
+    ///
+    /// With `filtering` and `pagination`, the list of codes honors the parameters of a `client::CodesQuery`,
+    /// except for `since`.
+    pub fn features(&self, features: &[&str]) {
+        self.state().features = Some(features.iter().map(|f| f.to_string()).collect());
+    }
+
+    /// Fails the next request with `fault`, faults queue up if called more than once.
+    pub fn fail_next(&self, fault: Fault) {
+        self.state().next_faults.push_back(fault);
+    }
+
+    /// Fails every request with `fault` until `clear_faults` is called.
+    pub fn fail_all(&self, fault: Fault) {
+        self.state().fault = Some(fault);
+    }
+
+    pub fn clear_faults(&self) {
+        let mut state = self.state();
+        state.fault = None;
+        state.next_faults.clear();
+    }
+
+    /// The codes the fake currently knows about, including inserted codes.
+    pub fn codes(&self) -> Vec<Code> {
+        self.state()
+            .codes
+            .iter()
+            .map(|(_, code)| code.clone())
+            .collect()
+    }
+
+    /// Every request the fake received, oldest first.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state().requests.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A panicking test must not break the fake for the other tests sharing it.
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+async fn serve(listener: TcpListener, state: Arc<Mutex<State>>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(connection(stream, state.clone()));
+    }
+}
+
+/// Handles a single request, the connection is closed after responding.
+async fn connection(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut stream = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    if stream.read_line(&mut request_line).await.is_err() {
+        return;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        match stream.read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) if line.trim().is_empty() => break,
+            Ok(_) => {}
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim().to_ascii_lowercase();
+            if name == "content-length" {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+            headers.insert(name, value.trim().to_string());
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    if stream.read_exact(&mut body).await.is_err() {
+        return;
+    }
+    let body = String::from_utf8_lossy(&body).to_string();
+
+    let (fault, response) = {
+        let mut state = lock(&state);
+        let authorized = authorize(&state, &method, &path, &headers, &body);
+        let path = path.strip_prefix("/v1").unwrap_or(&path).to_string();
+        state.requests.push(RecordedRequest {
+            method: method.clone(),
+            path: path.clone(),
+            body: body.clone(),
+        });
+
+        let fault = state.next_faults.pop_front().or(state.fault.clone());
+        let response = match &fault {
+            Some(Fault::Slow(_)) | None => handle(&mut state, &method, &path, authorized, &body),
+            Some(fault) => fault_response(fault),
+        };
+        (fault, response)
+    };
+
+    if let Some(Fault::Slow(delay)) = fault {
+        tokio::time::sleep(delay).await;
+    }
+
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason(response.status),
+        response.body.len()
+    );
+    for (name, value) in response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    let stream = stream.get_mut();
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(response.body.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+fn fault_response(fault: &Fault) -> Response {
+    match fault {
+        Fault::NotFound => Response::error(404, "The requested resource could not be found."),
+        Fault::Unauthorized => Response::error(401, "Invalid API key"),
+        Fault::Unprocessable => Response::json(
+            422,
+            r#"{"error":{"code":422,"reason":"Unprocessable Entity","description":"The request was well-formed but was unable to be followed due to semantic errors."}}"#,
+        ),
+        Fault::InternalServerError => Response::error(500, "Internal Server Error"),
+        Fault::BadGateway => Response::json(502, "<html><body>502 Bad Gateway</body></html>"),
+        Fault::RateLimited(retry_after) => {
+            let mut response = Response::error(429, "Too Many Requests");
+            if let Some(retry_after) = retry_after {
+                response
+                    .headers
+                    .push(("Retry-After", retry_after.to_string()));
+            }
+            response
+        }
+        Fault::MalformedJson => Response::json(200, r#"{"codes": [{"code": "#),
+        Fault::Slow(_) => unreachable!("slow requests are handled as usual"),
+    }
+}
+
+/// Whether the request has credentials for the API key of the fake, if one is set.
+/// `path` is the path and query as requested, which signatures cover.
+fn authorize(
+    state: &State,
+    method: &str,
+    path: &str,
+    headers: &HashMap<String, String>,
+    body: &str,
+) -> bool {
+    let accepts = |key: &str| {
+        state
+            .api_key
+            .as_deref()
+            .is_none_or(|expected| expected == key)
+    };
+
+    if let Some(api_key) = headers.get("x-api-key") {
+        return accepts(api_key);
+    }
+
+    let bearer = headers
+        .get("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let Some(token) = bearer {
+        return accepts(token);
+    }
+
+    #[cfg(feature = "signing")]
+    if headers.contains_key("x-signature") {
+        return verify_signature(state, method, path, headers, body);
+    }
+
+    #[cfg(not(feature = "signing"))]
+    let _ = (method, path, body);
+
+    false
+}
+
+/// Checks the headers of a `client::HmacSigner`, the signature only if the fake has an API key.
+#[cfg(feature = "signing")]
+fn verify_signature(
+    state: &State,
+    method: &str,
+    path: &str,
+    headers: &HashMap<String, String>,
+    body: &str,
+) -> bool {
+    use sha2::{Digest, Sha256};
+
+    let header = |name: &str| headers.get(name).map(String::as_str).unwrap_or_default();
+    let body_sha256: String = Sha256::digest(body.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    if header("x-content-sha256") != body_sha256 {
+        return false;
+    }
+
+    let Some(api_key) = &state.api_key else {
+        return true;
+    };
+    let Ok(timestamp) = header("x-signature-timestamp").parse() else {
+        return false;
+    };
+
+    let signer = crate::client::HmacSigner::new(crate::api_key::ApiKey::new(api_key.clone()));
+    let expected = signer.signature(
+        method,
+        path,
+        timestamp,
+        header("x-signature-nonce"),
+        &body_sha256,
+    );
+
+    header("x-signature") == expected
+}
+
+fn handle(state: &mut State, method: &str, path: &str, authorized: bool, body: &str) -> Response {
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    if method != "GET" && !authorized {
+        return Response::error(401, "Invalid API key");
+    }
+
+    match (method, segments.as_slice()) {
+        ("GET", ["codes"]) => Response::json(200, codes_json(state, query)),
+        ("GET", ["info"]) if state.features.is_some() => Response::json(
+            200,
+            serde_json::json!({ "version": env!("CARGO_PKG_VERSION"), "api_version": 1, "features": state.features }),
+        ),
+        ("GET", ["sources"]) => Response::json(
+            200,
+            serde_json::json!({ "sources": state.sources.iter().map(|(id, s)| (id.to_string(), source_json(s))).collect::<serde_json::Map<_, _>>() }),
+        ),
+        ("GET", ["sources", id]) => match id.parse().ok().and_then(|id| state.sources.get(&id)) {
+            Some(source) => Response::json(200, source_json(source)),
+            None => Response::error(404, "The requested resource could not be found."),
+        },
+        ("PUT", ["codes"]) => insert(state, body),
+        ("PATCH", ["codes", code]) => match find(state, code) {
+            Some(index) => update(state, index, body),
+            None => Response::error(404, "Unknown code"),
+        },
+        ("POST", ["codes", code, "expire"]) => match find(state, code) {
+            Some(index) => {
+                state.codes[index].1.expired = true;
+                Response::json(200, "")
+            }
+            None => Response::error(404, "Unknown code"),
+        },
+        ("DELETE", ["codes", code]) => match find(state, code) {
+            Some(index) => {
+                state.codes.remove(index);
+                Response::json(200, "")
+            }
+            None => Response::error(404, "Unknown code"),
+        },
+        _ => Response::error(404, "The requested resource could not be found."),
+    }
+}
+
+/// The index of the code in the route, which is percent-encoded.
+fn find(state: &State, code: &str) -> Option<usize> {
+    let code: ChestCode = percent_decode(code).parse().ok()?;
+
+    state.codes.iter().position(|(_, c)| c.code == code)
+}
+
+fn percent_decode(value: &str) -> String {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+
+    while let Some(byte) = bytes.next() {
+        if byte != b'%' {
+            decoded.push(byte);
+            continue;
+        }
+        let hex: String = bytes.by_ref().take(2).map(char::from).collect();
+        decoded.push(u8::from_str_radix(&hex, 16).unwrap_or(b'?'));
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn insert(state: &mut State, body: &str) -> Response {
+    let Ok(request) = serde_json::from_str::<serde_json::Value>(body) else {
+        return Response::error(400, "Malformed request body");
+    };
+    let Some(code) = request["code"]
+        .as_str()
+        .and_then(|code| code.parse::<ChestCode>().ok())
+    else {
+        return Response::error(422, "Invalid code");
+    };
+
+    if state.codes.iter().any(|(_, c)| c.code == code) {
+        return Response::error(409, "Code already exists");
+    }
+
+    let creator = lookup_source(state, &request["creator_name"], &request["creator_url"]);
+    let submitter = lookup_source(state, &request["submitter_name"], &request["submitter_url"])
+        .or_else(|| creator.clone());
+
+    let id = state.next_id;
+    state.next_id += 1;
+    state.codes.push((
+        id,
+        Code {
+            code,
+            expired: false,
+            expires_at: request["expires_at"].as_u64().map(|unix| unix.to_string()),
+            creator,
+            submitter,
+            lister: None,
+        },
+    ));
+
+    Response::json(200, id)
+}
+
+fn update(state: &mut State, index: usize, body: &str) -> Response {
+    let Ok(request) = serde_json::from_str::<serde_json::Value>(body) else {
+        return Response::error(400, "Malformed request body");
+    };
+
+    let creator = lookup_source(state, &request["creator_name"], &request["creator_url"]);
+    let submitter = lookup_source(state, &request["submitter_name"], &request["submitter_url"]);
+    let code = &mut state.codes[index].1;
+
+    if let Some(expires_at) = request["expires_at"].as_u64() {
+        code.expires_at = Some(expires_at.to_string());
+    }
+    if let Some(expired) = request["expired"].as_bool() {
+        code.expired = expired;
+    }
+    if creator.is_some() {
+        code.creator = creator;
+    }
+    if submitter.is_some() {
+        code.submitter = submitter;
+    }
+
+    Response::json(200, "")
+}
+
+/// Finds the source with this name and URL, or creates it.
+fn lookup_source(
+    state: &mut State,
+    name: &serde_json::Value,
+    url: &serde_json::Value,
+) -> Option<Source> {
+    let (name, url) = (name.as_str()?, url.as_str()?);
+
+    if let Some(source) = state
+        .sources
+        .values()
+        .find(|s| s.name == name && s.url == url)
+    {
+        return Some(source.clone());
+    }
+
+    let id = state.sources.keys().max().map_or(1, |id| id + 1);
+    let source = Source {
+        id,
+        name: name.to_string(),
+        url: url.to_string(),
+    };
+    state.sources.insert(id, source.clone());
+
+    Some(source)
+}
+
+/// The list of codes, filtered and paginated by the query parameters if the fake has these features.
+fn codes_json(state: &State, query: &str) -> serde_json::Value {
+    let source_id = |source: &Option<Source>| source.as_ref().map_or(0, |s| s.id);
+    let supports = |feature: &str| {
+        state
+            .features
+            .as_ref()
+            .is_some_and(|features| features.iter().any(|f| f == feature))
+    };
+
+    let params: HashMap<String, String> = query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .map(|(name, value)| (name.to_string(), percent_decode(value)))
+        .collect();
+    let param = |name: &str| match supports("filtering") {
+        true => params.get(name),
+        false => None,
+    };
+
+    let mut matching: Vec<&Code> = state
+        .codes
+        .iter()
+        .map(|(_, code)| code)
+        .filter(|code| param("expired").is_none_or(|expired| *expired == code.expired.to_string()))
+        .filter(|code| {
+            param("creator").is_none_or(|id| *id == source_id(&code.creator).to_string())
+        })
+        .filter(|code| param("lister").is_none_or(|id| *id == source_id(&code.lister).to_string()))
+        .filter(|code| {
+            param("creator_name").is_none_or(|name| {
+                code.creator
+                    .as_ref()
+                    .is_some_and(|creator| creator.name.eq_ignore_ascii_case(name))
+            })
+        })
+        .collect();
+
+    let mut next_cursor = None;
+    if supports("pagination") {
+        let number = |name: &str| {
+            params
+                .get(name)
+                .and_then(|value| value.parse::<usize>().ok())
+        };
+        let offset = number("cursor").or(number("offset")).unwrap_or(0);
+        let limit = number("limit").unwrap_or(usize::MAX);
+
+        if matching.len() > offset.saturating_add(limit) {
+            next_cursor = Some((offset + limit).to_string());
+        }
+        matching = matching.into_iter().skip(offset).take(limit).collect();
+    }
+
+    let codes: Vec<serde_json::Value> = matching
+        .into_iter()
+        .map(|code| {
+            serde_json::json!({
+                "code": code.code,
+                "expired": code.expired,
+                "expires_at": code.expires_at.clone().unwrap_or_default(),
+                "sources": {
+                    "creator": source_id(&code.creator),
+                    "submitter": source_id(&code.submitter),
+                    "lister": source_id(&code.lister),
+                },
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "codes": codes,
+        "sources": state.sources.iter().map(|(id, s)| (id.to_string(), source_json(s))).collect::<serde_json::Map<_, _>>(),
+        "next_cursor": next_cursor,
+    })
+}
+
+fn source_json(source: &Source) -> serde_json::Value {
+    serde_json::json!({ "id": source.id, "name": source.name, "url": source.url })
+}
+
+fn reason(status: u16) -> &'static str {
+    reqwest::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("Unknown")
+}
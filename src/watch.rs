@@ -0,0 +1,266 @@
+use crate::client::error::ClientError;
+use crate::client::CodesClient;
+use crate::seen::SeenStore;
+use crate::{ChestCode, Code, Source};
+use futures_util::Stream;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The default time between two polls of the remote.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(300);
+
+/// A change in the list of codes, as observed by a `CodeWatcher`.
+#[derive(Clone, Debug)]
+pub enum WatchEvent {
+    /// A code was published that the watcher did not know about yet.
+    CodeAdded(Code),
+    /// A known code is now marked as expired by the remote.
+    CodeExpired(Code),
+    /// A known code is no longer listed by the remote.
+    CodeRemoved(Code),
+    /// The creator, submitter or lister of a known code changed.
+    SourceChanged {
+        previous: Box<Code>,
+        current: Box<Code>,
+    },
+}
+
+/// CodeWatcher polls `CodesClient::get_codes` on an interval and reports what changed between polls.
+///
+/// Codes are identified by `Code::code`. Without seeding, the first poll reports every listed code as added,
+/// use `seed` with the codes you already processed, or `seen_store` to persist which codes were reported,
+/// to only hear about new changes after a restart.
+///
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use licc::client::CodesClient;
+/// use licc::watch::{CodeWatcher, WatchEvent};
+/// use std::time::Duration;
+///
+/// # async fn run() {
+/// let events = CodeWatcher::new(CodesClient::default())
+///     .interval(Duration::from_secs(60))
+///     .into_stream();
+/// futures_util::pin_mut!(events);
+///
+/// while let Some(event) = events.next().await {
+///     if let Ok(WatchEvent::CodeAdded(code)) = event {
+///         println!("New code: {}", code.code);
+///     }
+/// }
+/// # }
+/// ```
+pub struct CodeWatcher {
+    client: CodesClient,
+    interval: Duration,
+    known: HashMap<ChestCode, Code>,
+    seen: Option<Arc<dyn SeenStore>>,
+}
+
+impl CodeWatcher {
+    pub fn new(client: CodesClient) -> Self {
+        Self {
+            client,
+            interval: DEFAULT_INTERVAL,
+            known: HashMap::new(),
+            seen: None,
+        }
+    }
+
+    /// Time between two polls of the remote, defaults to 5 minutes.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Mark codes as already known, so they are not reported as added on the first poll.
+    pub fn seed(mut self, codes: impl IntoIterator<Item = Code>) -> Self {
+        self.known
+            .extend(codes.into_iter().map(|code| (code.code.clone(), code)));
+        self
+    }
+
+    /// Only report a code as added if it is not yet recorded in `store`, and record every added code.
+    /// If the store cannot be read or written, the code is reported anyway:
+    /// reporting a code twice is considered better than never reporting it.
+    pub fn seen_store(mut self, store: Arc<dyn SeenStore>) -> Self {
+        self.seen = Some(store);
+        self
+    }
+
+    /// The codes as of the last successful poll, including seeded codes.
+    pub fn known(&self) -> impl Iterator<Item = &Code> {
+        self.known.values()
+    }
+
+    /// Query the remote once and return what changed since the previous poll.
+    pub async fn poll(&mut self) -> Result<Vec<WatchEvent>, ClientError> {
+        let codes = self.client.get_codes().await?;
+        let mut events = diff(&self.known, &codes);
+
+        if let Some(seen) = &self.seen {
+            events.retain(|event| match event {
+                WatchEvent::CodeAdded(code) => seen.record(code.code.as_str()).unwrap_or(true),
+                _ => true,
+            });
+        }
+
+        self.known = codes
+            .into_iter()
+            .map(|code| (code.code.clone(), code))
+            .collect();
+
+        Ok(events)
+    }
+
+    /// Poll the remote forever, yielding every change as it is observed.
+    ///
+    /// The first poll happens immediately. Failed polls are yielded as errors and retried at the next interval,
+    /// the stream never ends on its own.
+    pub fn into_stream(self) -> impl Stream<Item = Result<WatchEvent, ClientError>> {
+        let state = (self, VecDeque::new(), true);
+
+        futures_util::stream::unfold(state, |(mut watcher, mut pending, mut first)| async move {
+            loop {
+                if let Some(event) = pending.pop_front() {
+                    return Some((Ok(event), (watcher, pending, first)));
+                }
+
+                if !first {
+                    tokio::time::sleep(watcher.interval).await;
+                }
+                first = false;
+
+                match watcher.poll().await {
+                    Ok(events) => pending.extend(events),
+                    Err(err) => return Some((Err(err), (watcher, pending, first))),
+                }
+            }
+        })
+    }
+}
+
+/// Compare the previously known codes against the currently listed codes.
+/// Events are ordered: removals first, then the current codes in the order the remote listed them.
+fn diff(known: &HashMap<ChestCode, Code>, current: &[Code]) -> Vec<WatchEvent> {
+    let mut events: Vec<WatchEvent> = Vec::new();
+    let listed: HashSet<&ChestCode> = current.iter().map(|code| &code.code).collect();
+
+    let mut removed: Vec<&Code> = known
+        .values()
+        .filter(|code| !listed.contains(&code.code))
+        .collect();
+    removed.sort_by(|a, b| a.code.cmp(&b.code));
+    events.extend(removed.into_iter().cloned().map(WatchEvent::CodeRemoved));
+
+    for code in current {
+        let previous = match known.get(&code.code) {
+            Some(previous) => previous,
+            None => {
+                events.push(WatchEvent::CodeAdded(code.clone()));
+                continue;
+            }
+        };
+
+        if !previous.expired && code.expired {
+            events.push(WatchEvent::CodeExpired(code.clone()));
+        }
+
+        if sources_differ(previous, code) {
+            events.push(WatchEvent::SourceChanged {
+                previous: Box::new(previous.clone()),
+                current: Box::new(code.clone()),
+            });
+        }
+    }
+
+    events
+}
+
+/// Slim codes carry no sources, so a missing source on either side is not considered a change.
+fn sources_differ(previous: &Code, current: &Code) -> bool {
+    let differs = |a: &Option<Source>, b: &Option<Source>| match (a, b) {
+        (Some(a), Some(b)) => a.id != b.id,
+        _ => false,
+    };
+
+    differs(&previous.creator, &current.creator)
+        || differs(&previous.submitter, &current.submitter)
+        || differs(&previous.lister, &current.lister)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn code(code: &str, expired: bool, creator: i32) -> Code {
+        Code {
+            code: code.parse().unwrap(),
+            expired,
+            expires_at: None,
+            creator: Some(Source {
+                id: creator,
+                name: format!("creator {}", creator),
+                url: "https://creator.example".to_string(),
+            }),
+            submitter: None,
+            lister: None,
+        }
+    }
+
+    fn known(codes: &[Code]) -> HashMap<ChestCode, Code> {
+        codes.iter().map(|c| (c.code.clone(), c.clone())).collect()
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        let codes = vec![code("AAAA-BBBB-CCCC", false, 1)];
+        assert!(diff(&known(&codes), &codes).is_empty());
+    }
+
+    #[test]
+    fn test_diff_added_and_removed() {
+        let before = known(&[code("AAAA-BBBB-CCCC", false, 1)]);
+        let events = diff(&before, &[code("DDDD-EEEE-FFFF", false, 1)]);
+
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], WatchEvent::CodeRemoved(c) if c.code == "AAAA-BBBB-CCCC"));
+        assert!(matches!(&events[1], WatchEvent::CodeAdded(c) if c.code == "DDDD-EEEE-FFFF"));
+    }
+
+    #[test]
+    fn test_diff_expired() {
+        let before = known(&[code("AAAA-BBBB-CCCC", false, 1)]);
+        let events = diff(&before, &[code("AAAA-BBBB-CCCC", true, 1)]);
+
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], WatchEvent::CodeExpired(c) if c.code == "AAAA-BBBB-CCCC"));
+
+        let before = known(&[code("AAAA-BBBB-CCCC", true, 1)]);
+        assert!(diff(&before, &[code("AAAA-BBBB-CCCC", true, 1)]).is_empty());
+    }
+
+    #[test]
+    fn test_diff_source_changed() {
+        let before = known(&[code("AAAA-BBBB-CCCC", false, 1)]);
+        let events = diff(&before, &[code("AAAA-BBBB-CCCC", false, 2)]);
+
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0],
+            WatchEvent::SourceChanged { previous, current }
+                if previous.creator.as_ref().unwrap().id == 1 && current.creator.as_ref().unwrap().id == 2
+        ));
+    }
+
+    #[test]
+    fn test_seed() {
+        let watcher = CodeWatcher::new(CodesClient::default()).seed(vec![
+            code("AAAA-BBBB-CCCC", false, 1),
+            code("DDDD-EEEE-FFFF", false, 1),
+        ]);
+
+        assert_eq!(watcher.known().count(), 2);
+    }
+}
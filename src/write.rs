@@ -1,12 +1,33 @@
 #![cfg(feature = "write")]
 
+use crate::ChestCode;
+use std::time::Duration;
+
+/// The unix timestamp `duration` from now, for use as `InsertCodeRequest::expires_at`.
+/// Durations beyond the range of a unix timestamp are clamped to its maximum.
+pub fn expires_in(duration: Duration) -> u64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    now.saturating_add(duration).as_secs()
+}
+
+/// The unix timestamp of `datetime`, for use as `InsertCodeRequest::expires_at`.
+/// Moments before the unix epoch are clamped to it.
+#[cfg(feature = "chrono")]
+pub fn expires_at(datetime: chrono::DateTime<chrono::Utc>) -> u64 {
+    u64::try_from(datetime.timestamp()).unwrap_or(0)
+}
+
 /// InsertCodeRequest is the request body for inserting a code into the database.
 /// You will also need an API Key to insert codes.
 #[derive(Clone, Debug)]
 pub struct InsertCodeRequest {
     /// The code itself that can be redeemed in-game.
-    pub code: String,
+    pub code: ChestCode,
     /// A unix timestamp of when the code expires, best guess - we recommend defaulting to next week if unknown.
+    /// See `expires_in` and `expires_at` to compute it.
     pub expires_at: u64,
     /// The creator is the person who "created" the code. This is usually a streamer or developer.
     pub creator: SourceLookup,
@@ -14,6 +35,35 @@ pub struct InsertCodeRequest {
     pub submitter: Option<SourceLookup>,
 }
 
+/// UpdateCodeRequest is the request body for correcting an existing code.
+/// Fields left to None are not changed. You will also need an API Key to update codes.
+#[derive(Clone, Debug)]
+pub struct UpdateCodeRequest {
+    /// The code to update, this cannot be changed.
+    pub code: ChestCode,
+    /// A unix timestamp of when the code expires.
+    pub expires_at: Option<u64>,
+    /// Whether the code no longer works in-game, see also `CodesClient::expire_code`.
+    pub expired: Option<bool>,
+    /// The person who "created" the code.
+    pub creator: Option<SourceLookup>,
+    /// The person who submitted the code to some kind of list or channel.
+    pub submitter: Option<SourceLookup>,
+}
+
+impl UpdateCodeRequest {
+    /// An update of `code` that does not change anything yet.
+    pub fn new(code: ChestCode) -> Self {
+        Self {
+            code,
+            expires_at: None,
+            expired: None,
+            creator: None,
+            submitter: None,
+        }
+    }
+}
+
 /// SourceLookup represents a source of a code, such as a streamer or developer.
 /// This object is used for PUT/POST requests and do not require an ID.
 ///
@@ -26,3 +76,54 @@ pub struct SourceLookup {
     pub name: String,
     pub url: String,
 }
+
+/// UpdateSourceRequest is the request body for correcting an existing source.
+/// Fields left to None are not changed. You will also need an API Key to update sources.
+#[derive(Clone, Debug)]
+pub struct UpdateSourceRequest {
+    /// The ID of the source to update.
+    pub id: i32,
+    pub name: Option<String>,
+    pub url: Option<String>,
+}
+
+impl UpdateSourceRequest {
+    /// An update of the source `id` that does not change anything yet.
+    pub fn new(id: i32) -> Self {
+        Self {
+            id,
+            name: None,
+            url: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expires_in() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let expires_at = expires_in(Duration::from_secs(60));
+        assert!(expires_at >= now + 60 && expires_at <= now + 70);
+
+        assert_eq!(expires_in(Duration::MAX), u64::MAX);
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn test_expires_at() {
+        assert_eq!(
+            expires_at(chrono::DateTime::from_timestamp(800, 0).unwrap()),
+            800
+        );
+        assert_eq!(
+            expires_at(chrono::DateTime::from_timestamp(-800, 0).unwrap()),
+            0
+        );
+    }
+}
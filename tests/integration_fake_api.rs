@@ -0,0 +1,609 @@
+use licc::client::error::ClientError;
+use licc::client::{
+    Capability, CodesClient, CodesQuery, Hook, MirroredClient, RequestInfo, ResponseInfo,
+    RetryPolicy,
+};
+use licc::testing::{FakeServer, Fault};
+use licc::{Code, Source};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn seeded_code() -> Code {
+    let creator = Source {
+        id: 1,
+        name: "foo".to_string(),
+        url: "https://foo.example".to_string(),
+    };
+
+    Code {
+        creator: Some(creator.clone()),
+        submitter: Some(creator.clone()),
+        lister: Some(creator),
+        ..FakeServer::code("FOOB-BARS-TEST")
+    }
+}
+
+#[tokio::test]
+async fn test_get_codes() {
+    let server = FakeServer::start().await;
+    server.add_code(seeded_code());
+    let client = CodesClient::new_full(None, Some(server.base_url()), None);
+
+    let codes = client.get_codes().await.unwrap();
+
+    assert_eq!(codes.len(), 1);
+    assert_eq!(codes[0].code, "FOOB-BARS-TEST");
+    assert_eq!(codes[0].creator.as_ref().unwrap().name, "foo");
+    assert_eq!(client.get_sources().await.unwrap().len(), 1);
+    assert!(client.get_source(2).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_faults() {
+    let server = FakeServer::start().await;
+    let client = CodesClient::new_full(None, Some(server.base_url()), None);
+
+    server.fail_next(Fault::NotFound);
+    server.fail_next(Fault::Unprocessable);
+    server.fail_next(Fault::BadGateway);
+    server.fail_next(Fault::MalformedJson);
+    server.fail_next(Fault::RateLimited(Some(5)));
+
+    assert!(client.get_codes().await.unwrap_err().is_not_found());
+    assert!(matches!(
+        client.get_codes().await,
+        Err(ClientError::ServerError(err)) if err.error.code == 422
+    ));
+    assert!(matches!(
+        client.get_codes().await,
+        Err(ClientError::UnexpectedResponse { .. })
+    ));
+    assert!(matches!(
+        client.get_codes().await,
+        Err(ClientError::Serde(_))
+    ));
+    assert!(matches!(
+        client.get_codes().await,
+        Err(ClientError::RateLimited {
+            retry_after: Some(retry_after)
+        }) if retry_after == Duration::from_secs(5)
+    ));
+    assert!(client.get_codes().await.is_ok());
+}
+
+#[tokio::test]
+async fn test_retries_transient_faults() {
+    let server = FakeServer::start().await;
+    server.add_code(seeded_code());
+    server.fail_next(Fault::InternalServerError);
+    let client = CodesClient::builder()
+        .base_url(server.base_url())
+        .retry_policy(RetryPolicy::exponential(2).initial_backoff(Duration::from_millis(1)))
+        .build()
+        .unwrap();
+
+    assert_eq!(client.get_codes().await.unwrap().len(), 1);
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[derive(Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Hook for Recorder {
+    fn on_request(&self, request: &mut reqwest::Request, attempt: u32) {
+        let event = format!("request {} {}", request.url().path(), attempt);
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn on_response(&self, _request: &RequestInfo, response: &mut ResponseInfo) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("response {}", response.status.as_u16()));
+        response.body = response.body.replace("FOOB-BARS-TEST", "HOOK-BARS-TEST");
+    }
+
+    fn on_error(&self, request: &RequestInfo, _error: &ClientError) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("error {}", request.attempt));
+    }
+}
+
+#[tokio::test]
+async fn test_hooks() {
+    let server = FakeServer::start().await;
+    server.add_code(seeded_code());
+    server.fail_next(Fault::BadGateway);
+    let recorder = Recorder::default();
+    let events = recorder.0.clone();
+    let client = CodesClient::builder()
+        .base_url(server.base_url())
+        .retry_policy(RetryPolicy::exponential(2).initial_backoff(Duration::from_millis(1)))
+        .hook(recorder)
+        .build()
+        .unwrap();
+
+    assert_eq!(client.get_codes().await.unwrap()[0].code, "HOOK-BARS-TEST");
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            "request /v1/codes 1",
+            "response 502",
+            "error 1",
+            "request /v1/codes 2",
+            "response 200",
+        ]
+    );
+}
+
+#[tokio::test]
+#[cfg(feature = "metrics")]
+async fn test_metrics() {
+    let server = FakeServer::start().await;
+    server.add_code(seeded_code());
+    server.fail_next(Fault::BadGateway);
+    server.fail_next(Fault::NotFound);
+    let metrics = licc::client::Metrics::new();
+    let client = CodesClient::builder()
+        .base_url(server.base_url())
+        .retry_policy(RetryPolicy::exponential(2).initial_backoff(Duration::from_millis(1)))
+        .metrics(metrics.clone())
+        .build()
+        .unwrap();
+
+    assert!(client.get_codes().await.is_err());
+    assert_eq!(client.get_codes().await.unwrap().len(), 1);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.retries, 1);
+    assert_eq!(snapshot.errors.get("retries_exhausted"), Some(&1));
+    assert_eq!(snapshot.codes_returned.count, 1);
+    assert_eq!(snapshot.latency["/codes"].count, 3);
+
+    let text = snapshot.to_prometheus();
+    assert!(text.contains(r#"licc_requests_total{method="GET",route="/codes",status="502"} 1"#));
+    assert!(text.contains(r#"licc_requests_total{method="GET",route="/codes",status="404"} 1"#));
+    assert!(text.contains(r#"licc_requests_total{method="GET",route="/codes",status="200"} 1"#));
+}
+
+#[tokio::test]
+#[cfg(feature = "metrics")]
+async fn test_metrics_records_errors_outside_requests() {
+    let server = FakeServer::start().await;
+    server.features(&["filtering"]);
+    server.fail_next(Fault::MalformedJson);
+    let metrics = licc::client::Metrics::new();
+    let builder = || {
+        CodesClient::builder()
+            .base_url(server.base_url())
+            .metrics(metrics.clone())
+    };
+    let client = builder().build().unwrap();
+
+    assert!(matches!(
+        client.get_codes().await,
+        Err(ClientError::Serde(_))
+    ));
+    assert!(client.require(Capability::Pagination).await.is_err());
+    assert!(builder().base_url("not a url").build().is_err());
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.errors.get("serde"), Some(&1));
+    assert_eq!(snapshot.errors.get("unsupported"), Some(&1));
+    assert_eq!(snapshot.errors.get("invalid_base_url"), Some(&1));
+}
+
+#[tokio::test]
+#[cfg(feature = "metrics")]
+async fn test_metrics_skips_handled_errors() {
+    let server = FakeServer::start().await;
+    server.add_code(seeded_code());
+    let metrics = licc::client::Metrics::new();
+    let client = CodesClient::builder()
+        .base_url(server.base_url())
+        .metrics(metrics.clone())
+        .build()
+        .unwrap();
+
+    // The fake has no info endpoint and no source 42, the client handles both 404s.
+    assert!(!client.server_info().await.unwrap().discovered);
+    assert!(client.get_source(42).await.unwrap().is_none());
+    assert_eq!(
+        client
+            .query_codes(&CodesQuery::new().active())
+            .await
+            .unwrap()
+            .codes
+            .len(),
+        1
+    );
+
+    let snapshot = metrics.snapshot();
+    assert!(snapshot.errors.is_empty(), "{:?}", snapshot.errors);
+    assert!(snapshot.to_prometheus().contains(r#"status="404"} 1"#));
+}
+
+#[tokio::test]
+async fn test_server_info() {
+    let server = FakeServer::start().await;
+    let client = CodesClient::new_full(None, Some(server.base_url()), None);
+
+    let info = client.server_info().await.unwrap();
+    assert!(!info.discovered);
+    assert_eq!(info.supports(Capability::BatchInsert), None);
+
+    let server = FakeServer::start().await;
+    server.features(&["filtering"]);
+    let client = CodesClient::new_full(None, Some(server.base_url()), None);
+
+    let info = client.server_info().await.unwrap();
+    assert!(info.discovered);
+    assert_eq!(info.supports(Capability::Filtering), Some(true));
+    assert!(matches!(
+        client.require(Capability::Pagination).await,
+        Err(ClientError::Unsupported {
+            capability: Capability::Pagination
+        })
+    ));
+
+    client.clone().server_info().await.unwrap();
+    assert_eq!(server.requests().len(), 1);
+}
+
+fn seed_query_codes(server: &FakeServer) {
+    let other = Source {
+        id: 2,
+        name: "bar".to_string(),
+        url: "https://bar.example".to_string(),
+    };
+
+    server.add_code(seeded_code());
+    server.add_code(Code {
+        expired: true,
+        ..seeded_code()
+    });
+    server.add_code(Code {
+        creator: Some(other),
+        ..seeded_code()
+    });
+    server.add_code(seeded_code());
+}
+
+#[tokio::test]
+async fn test_query_codes_client_side() {
+    use futures_util::StreamExt;
+
+    let server = FakeServer::start().await;
+    seed_query_codes(&server);
+    let client = CodesClient::new_full(None, Some(server.base_url()), None);
+    let query = CodesQuery::new().active().creator_name("FOO").limit(1);
+
+    let page = client.query_codes(&query).await.unwrap();
+    assert_eq!(page.codes.len(), 1);
+    let page = client.query_codes(&page.next.unwrap()).await.unwrap();
+    assert_eq!(page.codes.len(), 1);
+    assert!(page.next.is_none());
+
+    let codes: Vec<Code> = client
+        .query_codes_stream(query)
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert_eq!(codes.len(), 2);
+    assert!(server
+        .requests()
+        .iter()
+        .filter(|request| request.path != "/info")
+        .all(|request| request.path == "/codes"));
+}
+
+#[tokio::test]
+async fn test_query_codes_without_server_info() {
+    let server = FakeServer::start().await;
+    seed_query_codes(&server);
+    server.features(&["filtering", "pagination"]);
+    let client = CodesClient::new_full(None, Some(server.base_url()), None);
+    let query = CodesQuery::new().active().creator_name("FOO");
+
+    for fault in [Fault::InternalServerError, Fault::MalformedJson] {
+        server.fail_next(fault);
+        let page = client.query_codes(&query).await.unwrap();
+        assert_eq!(page.codes.len(), 2);
+    }
+
+    let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+    assert_eq!(paths, ["/info", "/codes", "/info", "/codes"]);
+}
+
+#[tokio::test]
+async fn test_query_codes_server_side() {
+    use futures_util::StreamExt;
+
+    let server = FakeServer::start().await;
+    server.features(&["filtering", "pagination"]);
+    seed_query_codes(&server);
+    let client = CodesClient::new_full(None, Some(server.base_url()), None);
+    let query = CodesQuery::new().active().creator(1).limit(1);
+
+    let page = client.query_codes(&query).await.unwrap();
+    assert_eq!(page.codes.len(), 1);
+    assert_eq!(page.next, Some(query.clone().cursor("1")));
+
+    let codes: Vec<Code> = client
+        .query_codes_stream(query)
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert_eq!(codes.len(), 2);
+
+    let paths: Vec<String> = server
+        .requests()
+        .into_iter()
+        .map(|request| request.path)
+        .filter(|path| path.starts_with("/codes"))
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "/codes?expired=false&creator=1&limit=1",
+            "/codes?expired=false&creator=1&limit=1",
+            "/codes?expired=false&creator=1&limit=1&cursor=1",
+        ]
+    );
+    let info_requests = server
+        .requests()
+        .iter()
+        .filter(|r| r.path == "/info")
+        .count();
+    assert_eq!(info_requests, 1);
+}
+
+#[tokio::test]
+async fn test_slow_response_times_out() {
+    let server = FakeServer::start().await;
+    server.fail_all(Fault::Slow(Duration::from_secs(5)));
+    let client = CodesClient::builder()
+        .base_url(server.base_url())
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+
+    assert!(client.get_codes().await.unwrap_err().is_timeout());
+}
+
+#[tokio::test]
+async fn test_mirror_failover_and_merge() {
+    let primary = FakeServer::start().await;
+    let secondary = FakeServer::start().await;
+    primary.add_code(FakeServer::code("FOOB-BARS-TEST"));
+    primary.add_source(Source {
+        id: 7,
+        ..seeded_code().creator.unwrap()
+    });
+    secondary.add_code(seeded_code());
+    secondary.add_code(FakeServer::code("OTHE-RCOD-ETST"));
+    let client = MirroredClient::new(primary.base_url(), [secondary.base_url()]).unwrap();
+
+    primary.fail_next(Fault::BadGateway);
+    let served = client.get_codes().await.unwrap();
+    assert_eq!(served.mirror, secondary.base_url());
+    assert!(!client.status()[0].healthy);
+
+    let served = client.get_codes().await.unwrap();
+    assert_eq!(served.mirror, secondary.base_url());
+
+    let merged = client.get_codes_merged().await.unwrap();
+    assert_eq!(merged.mirrors.len(), 2);
+    assert_eq!(merged.codes.len(), 2);
+    assert_eq!(merged.codes[0].mirror, primary.base_url());
+    assert_eq!(merged.codes[0].value.creator.as_ref().unwrap().id, 7);
+    assert_eq!(merged.codes[1].mirror, secondary.base_url());
+    assert!(merged.codes[1].value.creator.is_none());
+    assert!(client.status()[0].healthy);
+}
+
+#[cfg(feature = "write")]
+mod write {
+    use super::*;
+    use licc::api_key::ApiKey;
+    use licc::write::{expires_in, InsertCodeRequest, SourceLookup, UpdateCodeRequest};
+
+    fn insert_request(code: &str) -> InsertCodeRequest {
+        InsertCodeRequest {
+            code: code.parse().unwrap(),
+            expires_at: expires_in(Duration::from_secs(604800)),
+            creator: SourceLookup {
+                name: "Example Creator".to_string(),
+                url: "https://creator.example.org".to_string(),
+            },
+            submitter: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_requires_valid_api_key() {
+        let server = FakeServer::start().await;
+        server.api_key("secret");
+        let client = CodesClient::new_full(
+            Some(ApiKey::new("wrong".to_string())),
+            Some(server.base_url()),
+            None,
+        );
+
+        let err = client
+            .insert_code(insert_request("FOOB-BARS-TEST"))
+            .await
+            .unwrap_err();
+
+        assert!(err.is_unauthorized());
+        assert!(server.codes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_insert_with_authenticators() {
+        use licc::client::BearerToken;
+
+        let server = FakeServer::start().await;
+        server.api_key("secret");
+        let client = |token: &str| {
+            CodesClient::builder()
+                .base_url(server.base_url())
+                .authenticator(BearerToken(ApiKey::new(token.to_string())))
+                .build()
+                .unwrap()
+        };
+
+        client("secret")
+            .insert_code(insert_request("FOOB-BARS-TEST"))
+            .await
+            .unwrap();
+        assert!(client("wrong")
+            .insert_code(insert_request("OTHE-RCOD-ETST"))
+            .await
+            .unwrap_err()
+            .is_unauthorized());
+
+        #[cfg(feature = "signing")]
+        {
+            use licc::client::HmacSigner;
+
+            let client = |key: &str| {
+                CodesClient::builder()
+                    .base_url(server.base_url())
+                    .authenticator(HmacSigner::new(ApiKey::new(key.to_string())))
+                    .build()
+                    .unwrap()
+            };
+
+            client("secret")
+                .insert_code(insert_request("SIGN-EDCO-DEXX"))
+                .await
+                .unwrap();
+            assert!(client("wrong")
+                .insert_code(insert_request("OTHE-RCOD-ETST"))
+                .await
+                .unwrap_err()
+                .is_unauthorized());
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "signing")]
+    async fn test_hooks_run_before_signing() {
+        use licc::client::HmacSigner;
+
+        struct RewriteCode;
+
+        impl Hook for RewriteCode {
+            fn on_request(&self, request: &mut reqwest::Request, _attempt: u32) {
+                let body = request.body().and_then(|body| body.as_bytes()).unwrap();
+                let body =
+                    String::from_utf8_lossy(body).replace("SIGN-EDCO-DEXX", "HOOK-EDCO-DEXX");
+                *request.body_mut() = Some(body.into());
+            }
+        }
+
+        let server = FakeServer::start().await;
+        server.api_key("secret");
+        let client = CodesClient::builder()
+            .base_url(server.base_url())
+            .authenticator(HmacSigner::new(ApiKey::new("secret".to_string())))
+            .hook(RewriteCode)
+            .build()
+            .unwrap();
+
+        client
+            .insert_code(insert_request("SIGN-EDCO-DEXX"))
+            .await
+            .unwrap();
+        assert_eq!(server.codes()[0].code, "HOOK-EDCO-DEXX");
+
+        assert!(server
+            .codes()
+            .iter()
+            .all(|code| code.code != "OTHE-RCOD-ETST"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "blocking")]
+    async fn test_blocking_insert_with_authenticator() {
+        use licc::client::BearerToken;
+
+        let server = FakeServer::start().await;
+        server.api_key("secret");
+        let base_url = server.base_url();
+
+        let result = tokio::task::spawn_blocking(move || {
+            licc::blocking::CodesClient::new_full(None, Some(base_url), None)
+                .with_authenticator(BearerToken(ApiKey::new("secret".to_string())))
+                .insert_code(insert_request("FOOB-BARS-TEST"))
+        })
+        .await
+        .unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(server.codes().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_insert_update_and_delete() {
+        let server = FakeServer::start().await;
+        server.api_key("secret");
+        server.add_code(seeded_code());
+        let client = CodesClient::new_full(
+            Some(ApiKey::new("secret".to_string())),
+            Some(server.base_url()),
+            None,
+        );
+
+        let report = client
+            .insert_codes(vec![
+                insert_request("FOOB-BARS-TEST"),
+                insert_request("L0V3-Y0UR-D1C3-!#$&"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(report.conflicts().count(), 1);
+        assert_eq!(report.inserted().count(), 1);
+
+        let code = "L0V3-Y0UR-D1C3-!#$&".parse().unwrap();
+        client.expire_code(&code).await.unwrap();
+        assert!(server.codes()[1].expired);
+
+        let mut update = UpdateCodeRequest::new(code.clone());
+        update.expired = Some(false);
+        client.update_code(update).await.unwrap();
+        assert!(!server.codes()[1].expired);
+
+        client.delete_code(&code).await.unwrap();
+        assert_eq!(server.codes().len(), 1);
+        assert!(client.delete_code(&code).await.unwrap_err().is_not_found());
+    }
+
+    #[tokio::test]
+    async fn test_insert_codes_uses_server_info() {
+        let server = FakeServer::start().await;
+        server.features(&[]);
+        let client = CodesClient::new_full(
+            Some(ApiKey::new("secret".to_string())),
+            Some(server.base_url()),
+            None,
+        );
+
+        let report = client
+            .insert_codes(vec![insert_request("FOOB-BARS-TEST")])
+            .await
+            .unwrap();
+        assert_eq!(report.inserted().count(), 1);
+        assert!(server.requests().iter().all(|r| r.path != "/codes/batch"));
+
+        assert!(matches!(
+            client.merge_sources(1, [2]).await,
+            Err(ClientError::Unsupported {
+                capability: Capability::SourceMerge
+            })
+        ));
+    }
+}
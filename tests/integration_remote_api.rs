@@ -13,7 +13,7 @@ async fn test_get_codes() {
 
     let first: &Code = response.first().unwrap();
 
-    assert!(first.code.len() > 12);
+    assert!(first.code.as_str().len() > 12);
 }
 
 #[tokio::test]